@@ -0,0 +1,66 @@
+use std::{fmt::Display, ops::Range};
+
+use hug_lexer::parser::line_column;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A message about the parsed program that doesn't stop it from being parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// What the message is about, in bytes from the start of the program.
+    pub span: Option<Range<usize>>,
+}
+
+impl Diagnostic {
+    pub fn warning(message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Range<usize>) -> Diagnostic {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Formats this diagnostic like [`Display`], followed by the line and column it points
+    /// to in `program` if it has a span.
+    pub fn render(&self, program: &str) -> String {
+        match &self.span {
+            Some(span) => {
+                let (line, column) = line_column(program, span.start);
+                format!("{} (at {}:{})", self, line, column)
+            }
+            None => self.to_string(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use std::cmp::Ordering;
+
+use hug_lexer::tokenizer::{KeywordKind, TokenKind};
+use hug_lib::{
+    error::{EvalError, TypeError},
+    span::Spanned,
+    value::HugValue,
+    Ident,
+};
+
+use crate::HugScope;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulus,
+    And,
+    Or,
+    IsEqualTo,
+    IsNotEqualTo,
+    LessThan,
+    GreaterThan,
+    LessThanOrEquals,
+    GreaterThanOrEquals,
+    /// `item in collection`, see [`HugValue::contains`].
+    In,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BinaryOperator {
+    pub fn from_token(kind: TokenKind) -> Option<BinaryOperator> {
+        Some(match kind {
+            TokenKind::Add => BinaryOperator::Add,
+            TokenKind::Subtract => BinaryOperator::Subtract,
+            TokenKind::Multiply => BinaryOperator::Multiply,
+            TokenKind::Divide => BinaryOperator::Divide,
+            TokenKind::Modulus => BinaryOperator::Modulus,
+            TokenKind::And => BinaryOperator::And,
+            TokenKind::Or => BinaryOperator::Or,
+            TokenKind::IsEqualTo => BinaryOperator::IsEqualTo,
+            TokenKind::IsNotEqualTo => BinaryOperator::IsNotEqualTo,
+            TokenKind::LessThan => BinaryOperator::LessThan,
+            TokenKind::GreaterThan => BinaryOperator::GreaterThan,
+            TokenKind::LessThanOrEquals => BinaryOperator::LessThanOrEquals,
+            TokenKind::GreaterThanOrEquals => BinaryOperator::GreaterThanOrEquals,
+            TokenKind::Keyword(KeywordKind::In) => BinaryOperator::In,
+            TokenKind::BinaryAnd => BinaryOperator::BitAnd,
+            TokenKind::BinaryOr => BinaryOperator::BitOr,
+            TokenKind::BinaryXOr => BinaryOperator::BitXor,
+            TokenKind::ShiftLeft => BinaryOperator::ShiftLeft,
+            TokenKind::ShiftRight => BinaryOperator::ShiftRight,
+            _ => return None,
+        })
+    }
+
+    /// The operator of a compound assignment like `+=`, which assigns `variable op value`.
+    pub fn from_compound_assignment(kind: TokenKind) -> Option<BinaryOperator> {
+        Some(match kind {
+            TokenKind::AddAssign => BinaryOperator::Add,
+            TokenKind::SubtractAssign => BinaryOperator::Subtract,
+            TokenKind::MultiplyAssign => BinaryOperator::Multiply,
+            TokenKind::DivideAssign => BinaryOperator::Divide,
+            TokenKind::ModulusAssign => BinaryOperator::Modulus,
+            _ => return None,
+        })
+    }
+
+    /// Checks if a comparison operator is true for two values ordered as `ordering`, other
+    /// operators never are.
+    pub fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            BinaryOperator::LessThan => ordering.is_lt(),
+            BinaryOperator::GreaterThan => ordering.is_gt(),
+            BinaryOperator::LessThanOrEquals => ordering.is_le(),
+            BinaryOperator::GreaterThanOrEquals => ordering.is_ge(),
+            _ => false,
+        }
+    }
+
+    /// How tightly this operator binds, operators with a higher precedence are grouped first
+    /// and operators with the same precedence are grouped from left to right. Bitwise operators
+    /// bind more tightly than comparisons (like in Rust), so `a & b == 0` is `(a & b) == 0`.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::IsEqualTo
+            | BinaryOperator::IsNotEqualTo
+            | BinaryOperator::LessThan
+            | BinaryOperator::GreaterThan
+            | BinaryOperator::LessThanOrEquals
+            | BinaryOperator::GreaterThanOrEquals
+            | BinaryOperator::In => 3,
+            BinaryOperator::BitOr => 4,
+            BinaryOperator::BitXor => 5,
+            BinaryOperator::BitAnd => 6,
+            BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => 7,
+            BinaryOperator::Add | BinaryOperator::Subtract => 8,
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulus => 9,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    /// `-x`, only for signed numbers.
+    Negate,
+    /// `!x`, only for bools.
+    Not,
+    /// `~x`, only for integers, see [`HugValue::bitwise_not`].
+    BitNot,
+}
+
+impl UnaryOperator {
+    pub fn from_token(kind: TokenKind) -> Option<UnaryOperator> {
+        Some(match kind {
+            TokenKind::Subtract => UnaryOperator::Negate,
+            TokenKind::Not => UnaryOperator::Not,
+            TokenKind::BinaryNot => UnaryOperator::BitNot,
+            _ => return None,
+        })
+    }
+}
+
+/// An argument of a function call.
+#[derive(Debug, Clone)]
+pub enum CallArg {
+    Positional(Expression),
+    /// `...array`, every element of the array is passed as a separate argument.
+    Spread(Expression),
+    /// `name = value`, passes `value` as the argument called `name`. These come after every
+    /// positional argument.
+    Named(Ident, Expression),
+}
+
+impl CallArg {
+    pub fn expression(&self) -> &Expression {
+        match self {
+            CallArg::Positional(expression)
+            | CallArg::Spread(expression)
+            | CallArg::Named(_, expression) => expression,
+        }
+    }
+
+    pub fn is_spread(&self) -> bool {
+        matches!(self, CallArg::Spread(_))
+    }
+
+    /// The name this argument is passed by, if it is passed by name.
+    pub fn name(&self) -> Option<Ident> {
+        match self {
+            CallArg::Named(name, _) => Some(*name),
+            _ => None,
+        }
+    }
+
+    /// Only renames the value, the name belongs to the called function.
+    pub(crate) fn rename(&mut self, from: Ident, to: Ident) {
+        match self {
+            CallArg::Positional(expression)
+            | CallArg::Spread(expression)
+            | CallArg::Named(_, expression) => expression.rename(from, to),
+        }
+    }
+}
+
+/// Settings for [`Expression::evaluate_with_options`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EvalOptions {
+    /// Evaluate indexing a map with a key it doesn't have to [`HugValue::Unit`], instead of
+    /// giving [`EvalError::MissingKey`].
+    pub missing_key_is_unit: bool,
+}
+
+/// An expression, each operand of which keeps the span of the source it was parsed from.
+/// The span of a whole expression is kept by whatever holds it, like the entry it is in.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum Expression {
+    Literal(HugValue),
+    /// `[a, b, c]`, evaluates to a [`HugValue::Array`] of the values of its elements.
+    ArrayLiteral(Vec<Expression>),
+    Variable(Ident),
+    Call {
+        function: Ident,
+        args: Vec<CallArg>,
+    },
+    /// `Point { x: 1, y: 2 }`, evaluates to a [`HugValue::Struct`] of the values of its fields.
+    StructLiteral {
+        type_name: Ident,
+        fields: Vec<(Ident, Expression)>,
+    },
+    /// `base.field`
+    FieldAccess {
+        base: Box<Spanned<Expression>>,
+        field: Ident,
+    },
+    /// `base?.field`, evaluates to [`HugValue::Unit`] without accessing `field` when `base`
+    /// is unit.
+    OptionalFieldAccess {
+        base: Box<Spanned<Expression>>,
+        field: Ident,
+    },
+    /// `base[index]`, see [`HugValue::checked_index`].
+    Index {
+        base: Box<Spanned<Expression>>,
+        index: Box<Spanned<Expression>>,
+    },
+    /// `base.method(args)`
+    MethodCall {
+        base: Box<Spanned<Expression>>,
+        method: Ident,
+        args: Vec<CallArg>,
+    },
+    /// A prefix operator, which binds more tightly than any binary operator.
+    UnaryOp {
+        op: UnaryOperator,
+        operand: Box<Spanned<Expression>>,
+    },
+    BinaryOp {
+        op: BinaryOperator,
+        lhs: Box<Spanned<Expression>>,
+        rhs: Box<Spanned<Expression>>,
+    },
+    /// `typeof value`, evaluates to the name of the type of `value` as a string, like `"Int32"`.
+    TypeOf(Box<Spanned<Expression>>),
+    /// `if condition { .. } else { .. }`, evaluates to the value of the branch that is taken.
+    /// Without an `else` it evaluates to [`HugValue::Unit`] when the condition is false.
+    If {
+        condition: Box<Spanned<Expression>>,
+        then_branch: HugScope,
+        else_branch: Option<HugScope>,
+    },
+}
+
+impl Expression {
+    /// Computes the value of this expression, looking up variables with `variable`. Calls,
+    /// method calls and `if`s need a running program and give [`EvalError::NonConstant`], as
+    /// do field accesses on anything but structs and optional field accesses on unit.
+    /// Only the arithmetic, bitwise, comparison, logical and `in` operators are supported out of
+    /// the binary operators for now. `&&` and `||` only evaluate their right operand if the left
+    /// one doesn't already decide the result.
+    pub fn evaluate(
+        &self,
+        variable: impl Fn(Ident) -> Option<HugValue>,
+    ) -> Result<HugValue, EvalError> {
+        self.evaluate_with(&variable, EvalOptions::default())
+    }
+
+    /// Computes the value of this expression like [`Expression::evaluate`], with `options`.
+    pub fn evaluate_with_options(
+        &self,
+        variable: impl Fn(Ident) -> Option<HugValue>,
+        options: EvalOptions,
+    ) -> Result<HugValue, EvalError> {
+        self.evaluate_with(&variable, options)
+    }
+
+    fn evaluate_with(
+        &self,
+        variable: &dyn Fn(Ident) -> Option<HugValue>,
+        options: EvalOptions,
+    ) -> Result<HugValue, EvalError> {
+        match self {
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::ArrayLiteral(elements) => Ok(HugValue::Array(
+                elements
+                    .iter()
+                    .map(|element| element.evaluate_with(variable, options))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Expression::StructLiteral { type_name, fields } => Ok(HugValue::Struct {
+                type_name: *type_name,
+                fields: fields
+                    .iter()
+                    .map(|(field, value)| Ok((*field, value.evaluate_with(variable, options)?)))
+                    .collect::<Result<_, EvalError>>()?,
+            }),
+            Expression::Variable(id) => variable(*id).ok_or(EvalError::UndefinedVariable(*id)),
+            Expression::TypeOf(value) => Ok(HugValue::String(
+                value
+                    .evaluate_with(variable, options)?
+                    .kind()
+                    .name()
+                    .to_string(),
+            )),
+            Expression::UnaryOp { op, operand } => {
+                let operand = operand.evaluate_with(variable, options)?;
+                match op {
+                    UnaryOperator::Negate => Ok((-operand)?),
+                    UnaryOperator::Not => Ok((!operand)?),
+                    UnaryOperator::BitNot => Ok(operand.bitwise_not()?),
+                }
+            }
+            Expression::BinaryOp {
+                op: BinaryOperator::And,
+                lhs,
+                rhs,
+            } => lhs
+                .evaluate_with(variable, options)?
+                .and_then(|| rhs.evaluate_with(variable, options)),
+            Expression::BinaryOp {
+                op: BinaryOperator::Or,
+                lhs,
+                rhs,
+            } => lhs
+                .evaluate_with(variable, options)?
+                .or_else(|| rhs.evaluate_with(variable, options)),
+            Expression::BinaryOp { op, lhs, rhs } => {
+                let (lhs, rhs) = (
+                    lhs.evaluate_with(variable, options)?,
+                    rhs.evaluate_with(variable, options)?,
+                );
+                match op {
+                    BinaryOperator::Add => Ok((lhs + rhs)?),
+                    BinaryOperator::Subtract => Ok((lhs - rhs)?),
+                    BinaryOperator::Multiply => Ok((lhs * rhs)?),
+                    BinaryOperator::Divide => lhs.checked_div(&rhs),
+                    BinaryOperator::Modulus => lhs.checked_rem(&rhs),
+                    BinaryOperator::BitAnd => Ok((lhs & rhs)?),
+                    BinaryOperator::BitOr => Ok((lhs | rhs)?),
+                    BinaryOperator::BitXor => Ok((lhs ^ rhs)?),
+                    BinaryOperator::ShiftLeft => Ok((lhs << rhs)?),
+                    BinaryOperator::ShiftRight => Ok((lhs >> rhs)?),
+                    BinaryOperator::IsEqualTo => Ok(HugValue::Bool(lhs.checked_eq(&rhs)?)),
+                    BinaryOperator::IsNotEqualTo => Ok(HugValue::Bool(!lhs.checked_eq(&rhs)?)),
+                    BinaryOperator::LessThan
+                    | BinaryOperator::GreaterThan
+                    | BinaryOperator::LessThanOrEquals
+                    | BinaryOperator::GreaterThanOrEquals => {
+                        let ordering = lhs.compare(&rhs)?;
+                        Ok(HugValue::Bool(ordering.is_some_and(|o| op.accepts(o))))
+                    }
+                    BinaryOperator::In => match rhs.contains(&lhs) {
+                        Some(found) => Ok(HugValue::Bool(found)),
+                        // Maps can only be checked for string keys
+                        None if matches!(rhs, HugValue::Map(_)) => {
+                            Err(TypeError::new("String", &lhs).into())
+                        }
+                        None => Err(TypeError::new("Array or Map", &rhs).into()),
+                    },
+                    _ => Err(EvalError::NonConstant),
+                }
+            }
+            Expression::Index { base, index } => {
+                let (base, index) = (
+                    base.evaluate_with(variable, options)?,
+                    index.evaluate_with(variable, options)?,
+                );
+                match base.checked_index(&index) {
+                    Err(EvalError::MissingKey(_)) if options.missing_key_is_unit => {
+                        Ok(HugValue::Unit)
+                    }
+                    result => result,
+                }
+            }
+            Expression::FieldAccess { base, field }
+            | Expression::OptionalFieldAccess { base, field } => {
+                match base.evaluate_with(variable, options)? {
+                    HugValue::Unit if matches!(self, Expression::OptionalFieldAccess { .. }) => {
+                        Ok(HugValue::Unit)
+                    }
+                    value @ HugValue::Struct { .. } => value
+                        .field(*field)
+                        .cloned()
+                        .ok_or(EvalError::UnknownField(*field)),
+                    _ => Err(EvalError::NonConstant),
+                }
+            }
+            Expression::Call { .. } | Expression::MethodCall { .. } | Expression::If { .. } => {
+                Err(EvalError::NonConstant)
+            }
+        }
+    }
+
+    /// Replaces operations whose result is already known with that result, currently only
+    /// `"a" + "b"` which becomes `"ab"`. Operands are folded first, so whole chains of
+    /// constant strings become a single literal.
+    pub fn fold_constants(self) -> Expression {
+        match self {
+            Expression::BinaryOp {
+                op: BinaryOperator::Add,
+                lhs,
+                rhs,
+            } => {
+                let lhs = lhs.map(Expression::fold_constants);
+                let rhs = rhs.map(Expression::fold_constants);
+                match (lhs.node, rhs.node) {
+                    (
+                        Expression::Literal(lhs @ HugValue::String(_)),
+                        Expression::Literal(rhs @ HugValue::String(_)),
+                    ) => Expression::Literal((lhs + rhs).expect("strings can always be added")),
+                    (lhs_node, rhs_node) => Expression::BinaryOp {
+                        op: BinaryOperator::Add,
+                        lhs: Box::new(Spanned::new(lhs_node, lhs.span)),
+                        rhs: Box::new(Spanned::new(rhs_node, rhs.span)),
+                    },
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// Checks if this expression can be evaluated without running the program, which is the
+    /// case for literals, arrays of constant elements, and operators and indexing on constant
+    /// operands.
+    pub fn is_constant(&self) -> bool {
+        match self {
+            Expression::Literal(_) => true,
+            Expression::ArrayLiteral(elements) => elements.iter().all(Expression::is_constant),
+            Expression::StructLiteral { fields, .. } => {
+                fields.iter().all(|(_, value)| value.is_constant())
+            }
+            Expression::UnaryOp { operand, .. } | Expression::TypeOf(operand) => {
+                operand.is_constant()
+            }
+            Expression::BinaryOp { lhs, rhs, .. } => lhs.is_constant() && rhs.is_constant(),
+            Expression::Index { base, index } => base.is_constant() && index.is_constant(),
+            _ => false,
+        }
+    }
+
+    /// Computes the value of a constant expression (see [`Expression::is_constant`]) with the
+    /// arithmetic of [`HugValue`], so `2 * 3` gives `6`. Anything else gives
+    /// [`EvalError::NonConstant`].
+    pub fn get_constant_value(&self) -> Result<HugValue, EvalError> {
+        if self.is_constant() {
+            self.evaluate(|_| None)
+        } else {
+            Err(EvalError::NonConstant)
+        }
+    }
+
+    /// Replaces every use of the variable or function `from` with `to`, see
+    /// [`HugScope::rename_ident`].
+    pub(crate) fn rename(&mut self, from: Ident, to: Ident) {
+        match self {
+            Expression::Literal(_) => (),
+            Expression::ArrayLiteral(elements) => elements
+                .iter_mut()
+                .for_each(|element| element.rename(from, to)),
+            Expression::StructLiteral { type_name, fields } => {
+                if *type_name == from {
+                    *type_name = to;
+                }
+                fields
+                    .iter_mut()
+                    .for_each(|(_, value)| value.rename(from, to));
+            }
+            Expression::Variable(id) => {
+                if *id == from {
+                    *id = to;
+                }
+            }
+            Expression::Call { function, args } => {
+                if *function == from {
+                    *function = to;
+                }
+                args.iter_mut().for_each(|arg| arg.rename(from, to));
+            }
+            Expression::FieldAccess { base, .. } | Expression::OptionalFieldAccess { base, .. } => {
+                base.rename(from, to)
+            }
+            Expression::MethodCall { base, args, .. } => {
+                base.rename(from, to);
+                args.iter_mut().for_each(|arg| arg.rename(from, to));
+            }
+            Expression::UnaryOp { operand, .. } | Expression::TypeOf(operand) => {
+                operand.rename(from, to)
+            }
+            Expression::BinaryOp { lhs, rhs, .. } => {
+                lhs.rename(from, to);
+                rhs.rename(from, to);
+            }
+            Expression::Index { base, index } => {
+                base.rename(from, to);
+                index.rename(from, to);
+            }
+            Expression::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                condition.rename(from, to);
+                then_branch.rename_unless_declared(from, to);
+                if let Some(branch) = else_branch {
+                    branch.rename_unless_declared(from, to);
+                }
+            }
+        }
+    }
+
+    /// Checks if this expression always evaluates to [`HugValue::Unit`], without running it.
+    /// Expressions that might not, like variables, are not unit.
+    pub fn is_unit(&self) -> bool {
+        match self {
+            Expression::Literal(HugValue::Unit) => true,
+            Expression::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                then_branch.yields_unit()
+                    && else_branch
+                        .as_ref()
+                        .is_none_or(|branch| branch.yields_unit())
+            }
+            _ => false,
+        }
+    }
+}
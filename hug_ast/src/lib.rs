@@ -1,25 +1,55 @@
-use std::fmt::Display;
+use std::{collections::HashMap, error::Error, fmt::Display, ops::Range};
 
-use hug_lexer::parser::TokenPair;
-use hug_lib::{value::HugValue, Ident};
-use parser::HugTreeParser;
+use diagnostic::Diagnostic;
+use expression::{CallArg, Expression};
+use hug_lexer::{
+    lex,
+    parser::TokenPair,
+    tokenizer::{LiteralKind, TokenKind},
+};
+use hug_lib::{
+    error::CallError,
+    span::Spanned,
+    value::{HugValue, TypeKind},
+    Ident,
+};
+use parser::{HugTreeParser, ParseError};
 
+pub mod diagnostic;
+pub mod expression;
 pub mod parser;
 
-#[derive(Debug, Clone)]
-pub enum HugTreeFunctionCallArg {
-    Variable(Ident),
-    Value(HugValue),
+/// Whether a definition can be used outside of the module it is defined in, set with the
+/// `public` and `private` keywords in front of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    #[default]
+    Private,
+    Public,
 }
 
 #[derive(Debug, Clone)]
 pub enum HugTreeEntry {
     ModuleDefinition {
         module: Ident,
+        body: HugScope,
     },
     ExternalTypeDefinition {
         _type: Ident,
     },
+    /// `type name { field: Type, .. }`, a type with named fields in declaration order.
+    TypeDefinition {
+        name: Ident,
+        fields: Vec<(Ident, TypeKind)>,
+        visibility: Visibility,
+    },
+    /// `enum name { Variant, Other = value, .. }`, the variants in declaration order along with
+    /// their explicit discriminant, if any.
+    EnumDefinition {
+        name: Ident,
+        variants: Vec<(Ident, Option<HugValue>)>,
+        visibility: Visibility,
+    },
     ExternalModuleDefinition {
         module: Ident,
         location: String,
@@ -27,42 +57,597 @@ pub enum HugTreeEntry {
     ExternalFunctionDefinition {
         function: Ident,
     },
+    FunctionDefinition {
+        function: Ident,
+        args: Vec<Ident>,
+        /// The type after the `:` of each argument, `None` for arguments that don't declare one.
+        arg_types: Vec<Option<TypeKind>>,
+        /// The type after `->`, `None` if the function doesn't declare one.
+        return_type: Option<TypeKind>,
+        body: HugScope,
+        visibility: Visibility,
+    },
     VariableDefinition {
         variable: Ident,
         value: HugValue,
+        /// The arguments of every custom annotation on this definition, by annotation name.
+        annotations: HashMap<Ident, HashMap<String, (LiteralKind, String)>>,
+        visibility: Visibility,
+    },
+    /// `variable = value`, changes the value of an already declared variable.
+    Assignment {
+        variable: Ident,
+        value: Expression,
     },
     FunctionCall {
         function: Ident,
-        args: Vec<HugTreeFunctionCallArg>,
+        args: Vec<CallArg>,
+    },
+    /// `return` with an optional value, which is [`HugValue::Unit`] when left out.
+    Return(Option<Expression>),
+    /// `use a.b.c`, makes the last name of `path` available in the enclosing scope.
+    Use {
+        path: Vec<Ident>,
     },
+    /// `while condition { .. }`, runs `body` for as long as `condition` is true.
+    While {
+        condition: Expression,
+        body: HugScope,
+    },
+    /// An expression used as a statement, evaluated where it appears among the other entries.
+    Expression(Expression),
+}
+
+/// A block of entries together with everything declared directly inside of it.
+#[derive(Debug, Clone, Default)]
+pub struct HugScope {
+    /// Every name declared in this scope, in declaration order.
+    pub idents: Vec<Ident>,
+    /// Values of declared names that are already known while parsing.
+    pub members: HashMap<Ident, HugValue>,
+    /// Every statement of this block, strictly in source order. Definitions and expression
+    /// statements are never reordered, so running them one after another runs their side
+    /// effects in the order they were written. Each entry keeps the source it was parsed
+    /// from, entries that weren't parsed have an empty span.
+    pub entries: Vec<Spanned<HugTreeEntry>>,
+    /// Values of `members` hidden by [`HugScope::push_shadow`], the last one is restored first.
+    shadowed: HashMap<Ident, Vec<Option<HugValue>>>,
+}
+
+impl HugScope {
+    pub fn new() -> HugScope {
+        HugScope::default()
+    }
+
+    pub fn declare(&mut self, id: Ident) {
+        if !self.is_declared(id) {
+            self.idents.push(id);
+        }
+    }
+
+    pub fn is_declared(&self, id: Ident) -> bool {
+        self.idents.contains(&id)
+    }
+
+    /// Returns the names declared in this scope that start with `prefix`, looked up in `idents`.
+    /// An exact match comes first, followed by the other names from shortest to longest. Use
+    /// [`HugTreeParser::complete`](parser::HugTreeParser::complete) to include enclosing scopes.
+    pub fn complete(&self, prefix: &str, idents: &HashMap<String, Ident>) -> Vec<Ident> {
+        complete(prefix, idents, |id| self.is_declared(id))
+    }
+
+    /// Returns the current value of `id` in this scope, which is the most recently pushed
+    /// shadow if there is one.
+    pub fn resolve(&self, id: Ident) -> Option<&HugValue> {
+        self.members.get(&id)
+    }
+
+    /// Like [`HugScope::resolve`], but falls back to the scopes enclosing this one, from the
+    /// innermost (last) one outward. A scope doesn't know its parents, as it is owned by the
+    /// entry it belongs to, so they have to be passed along, like the root scope for a
+    /// function body. [`HugTreeParser::lookup`](parser::HugTreeParser::lookup) does this for
+    /// the scopes that are being parsed.
+    pub fn lookup<'a>(&'a self, id: Ident, enclosing: &[&'a HugScope]) -> Option<&'a HugValue> {
+        self.resolve(id)
+            .or_else(|| enclosing.iter().rev().find_map(|scope| scope.resolve(id)))
+    }
+
+    /// Binds `id` to `value` until the matching [`HugScope::pop_shadow`], like a `let` in an
+    /// inner block hiding a variable with the same name.
+    pub fn push_shadow(&mut self, id: Ident, value: HugValue) {
+        let previous = self.members.insert(id, value);
+        self.shadowed.entry(id).or_default().push(previous);
+    }
+
+    /// Removes the last shadow of `id` pushed with [`HugScope::push_shadow`] and returns it,
+    /// bringing back the value `id` had before. Returns `None` if `id` isn't shadowed.
+    pub fn pop_shadow(&mut self, id: Ident) -> Option<HugValue> {
+        let previous = self.shadowed.get_mut(&id)?.pop()?;
+        match previous {
+            Some(value) => self.members.insert(id, value),
+            None => self.members.remove(&id),
+        }
+    }
+
+    /// The expression this block evaluates to, which is its last entry if that is an
+    /// expression. Blocks that end in any other entry evaluate to [`HugValue::Unit`].
+    pub fn value(&self) -> Option<&Expression> {
+        match self.entries.last().map(|entry| &entry.node) {
+            Some(HugTreeEntry::Expression(expression)) => Some(expression),
+            _ => None,
+        }
+    }
+
+    /// Checks if this block always evaluates to [`HugValue::Unit`], see [`Expression::is_unit`].
+    pub fn yields_unit(&self) -> bool {
+        self.value().is_none_or(Expression::is_unit)
+    }
+
+    /// The number of variables defined directly in this scope, which is how many slots a frame
+    /// for it needs. Variables of blocks inside of it, like a function body, are not counted.
+    pub fn local_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.node, HugTreeEntry::VariableDefinition { .. }))
+            .count()
+    }
+
+    /// Removes everything from this scope but keeps the allocated memory, like
+    /// [`HugTreeAnnotationState::reset`](parser::HugTreeAnnotationState::reset).
+    pub fn clear(&mut self) {
+        self.idents.clear();
+        self.members.clear();
+        self.entries.clear();
+        self.shadowed.clear();
+    }
+
+    /// Renames `from` to `to` everywhere in this scope (and the scopes inside of it), using
+    /// `idents` to find or create the [`Ident`] of `to`, which is returned. Inner scopes that
+    /// declare their own `from` are left alone, as are field and method names and every name
+    /// after the first one in a `use` path, which belong to other scopes.
+    pub fn rename_ident(
+        &mut self,
+        idents: &mut HashMap<String, Ident>,
+        from: Ident,
+        to: &str,
+    ) -> Result<Ident, RenameError> {
+        if !matches!(lex(to).as_slice(), [pair] if matches!(pair.token.kind, TokenKind::Identifier(_)))
+        {
+            return Err(RenameError::InvalidName(to.to_string()));
+        }
+
+        let to = match idents.get(to) {
+            Some(&id) if id == from => return Ok(id),
+            Some(&id) if self.is_declared(id) => return Err(RenameError::Conflict(id)),
+            Some(&id) => id,
+            None => {
+                let id = Ident(idents.len());
+                idents.insert(to.to_string(), id);
+                id
+            }
+        };
+
+        self.rename(from, to);
+        Ok(to)
+    }
+
+    fn rename(&mut self, from: Ident, to: Ident) {
+        let rename = |id: &mut Ident| {
+            if *id == from {
+                *id = to;
+            }
+        };
+
+        self.idents.iter_mut().for_each(rename);
+        if let Some(value) = self.members.remove(&from) {
+            self.members.insert(to, value);
+        }
+        if let Some(values) = self.shadowed.remove(&from) {
+            self.shadowed.insert(to, values);
+        }
+
+        for entry in self.entries.iter_mut() {
+            match &mut entry.node {
+                HugTreeEntry::ModuleDefinition { module, body } => {
+                    rename(module);
+                    body.rename_unless_declared(from, to);
+                }
+                HugTreeEntry::ExternalTypeDefinition { _type: id }
+                | HugTreeEntry::ExternalModuleDefinition { module: id, .. }
+                | HugTreeEntry::ExternalFunctionDefinition { function: id }
+                | HugTreeEntry::VariableDefinition { variable: id, .. } => rename(id),
+                HugTreeEntry::EnumDefinition { name, variants, .. } => {
+                    rename(name);
+                    for (variant, _) in variants.iter_mut() {
+                        rename(variant);
+                    }
+                }
+                HugTreeEntry::TypeDefinition { name, fields, .. } => {
+                    rename(name);
+                    for (_, _type) in fields.iter_mut() {
+                        if let TypeKind::Other(id) = _type {
+                            rename(id);
+                        }
+                    }
+                }
+                HugTreeEntry::FunctionDefinition {
+                    function,
+                    args,
+                    arg_types,
+                    return_type,
+                    body,
+                    ..
+                } => {
+                    rename(function);
+                    for _type in arg_types.iter_mut().chain([return_type]) {
+                        if let Some(TypeKind::Other(id)) = _type {
+                            rename(id);
+                        }
+                    }
+                    if !args.contains(&from) {
+                        body.rename_unless_declared(from, to);
+                    }
+                }
+                HugTreeEntry::Assignment { variable, value } => {
+                    rename(variable);
+                    value.rename(from, to);
+                }
+                HugTreeEntry::FunctionCall { function, args } => {
+                    rename(function);
+                    args.iter_mut().for_each(|arg| arg.rename(from, to));
+                }
+                HugTreeEntry::Return(value) => {
+                    if let Some(value) = value {
+                        value.rename(from, to);
+                    }
+                }
+                HugTreeEntry::Use { path } => rename(&mut path[0]),
+                HugTreeEntry::While { condition, body } => {
+                    condition.rename(from, to);
+                    body.rename_unless_declared(from, to);
+                }
+                HugTreeEntry::Expression(expression) => expression.rename(from, to),
+            }
+        }
+    }
+
+    /// Like [`HugScope::rename`], but only if `from` isn't redeclared by this scope.
+    fn rename_unless_declared(&mut self, from: Ident, to: Ident) {
+        if !self.is_declared(from) {
+            self.rename(from, to);
+        }
+    }
+
+    /// Moves every function target in this scope (and the scopes inside of it) by `offset`.
+    fn relocate(&mut self, offset: usize) {
+        for value in self.members.values_mut() {
+            if let HugValue::Function(target) = value {
+                *target += offset;
+            }
+        }
+
+        for entry in self.entries.iter_mut() {
+            match &mut entry.node {
+                HugTreeEntry::FunctionDefinition { body, .. }
+                | HugTreeEntry::ModuleDefinition { body, .. }
+                | HugTreeEntry::While { body, .. } => body.relocate(offset),
+                HugTreeEntry::Expression(Expression::If {
+                    then_branch,
+                    else_branch,
+                    ..
+                }) => {
+                    then_branch.relocate(offset);
+                    if let Some(branch) = else_branch {
+                        branch.relocate(offset);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Every ident in `idents` that starts with `prefix` and is `visible`, in the order described
+/// by [`HugScope::complete`]. Names of the same length are sorted alphabetically.
+pub(crate) fn complete(
+    prefix: &str,
+    idents: &HashMap<String, Ident>,
+    visible: impl Fn(Ident) -> bool,
+) -> Vec<Ident> {
+    let mut matches: Vec<(&str, Ident)> = idents
+        .iter()
+        .filter(|(name, id)| name.starts_with(prefix) && visible(**id))
+        .map(|(name, id)| (name.as_str(), *id))
+        .collect();
+
+    matches.sort_by_key(|(name, _)| (name.len(), *name));
+    matches.into_iter().map(|(_, id)| id).collect()
+}
+
+/// Collects entries into a scope, see the [`Extend`] implementation.
+impl FromIterator<HugTreeEntry> for HugScope {
+    fn from_iter<T: IntoIterator<Item = HugTreeEntry>>(iter: T) -> HugScope {
+        let mut scope = HugScope::new();
+        scope.extend(iter);
+        scope
+    }
+}
+
+/// Appends entries that weren't parsed to a scope, giving them an empty span. See the
+/// [`Extend`] implementation for spanned entries.
+impl Extend<HugTreeEntry> for HugScope {
+    fn extend<T: IntoIterator<Item = HugTreeEntry>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|entry| Spanned::new(entry, 0..0)));
+    }
+}
+
+/// Appends entries to a scope, declaring everything they define. Only variables get a
+/// member, as the target of a function is only known to the tree it was parsed into.
+impl Extend<Spanned<HugTreeEntry>> for HugScope {
+    fn extend<T: IntoIterator<Item = Spanned<HugTreeEntry>>>(&mut self, iter: T) {
+        for entry in iter {
+            match &entry.node {
+                HugTreeEntry::VariableDefinition {
+                    variable, value, ..
+                } => {
+                    self.declare(*variable);
+                    self.members.insert(*variable, value.clone());
+                }
+                HugTreeEntry::ModuleDefinition { module, .. }
+                | HugTreeEntry::ExternalModuleDefinition { module, .. } => self.declare(*module),
+                HugTreeEntry::ExternalTypeDefinition { _type }
+                | HugTreeEntry::TypeDefinition { name: _type, .. }
+                | HugTreeEntry::EnumDefinition { name: _type, .. } => self.declare(*_type),
+                HugTreeEntry::Use { path } => self.declare(*path.last().unwrap()),
+                HugTreeEntry::ExternalFunctionDefinition { function }
+                | HugTreeEntry::FunctionDefinition { function, .. } => self.declare(*function),
+                _ => (),
+            }
+            self.entries.push(entry);
+        }
+    }
+}
+
+/// Errors that can occur when renaming with [`HugScope::rename_ident`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameError {
+    /// The new name is already declared in the scope.
+    Conflict(Ident),
+    /// The new name can't be written as an identifier, like `1a`, `my name` or `let`.
+    InvalidName(String),
 }
 
-#[derive(Debug)]
+impl Display for RenameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenameError::Conflict(id) => write!(f, "{:?} is already declared", id),
+            RenameError::InvalidName(name) => write!(f, "`{}` is not a valid name", name),
+        }
+    }
+}
+
+impl Error for RenameError {}
+
+/// Errors that can occur when combining trees with [`link`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// More than one tree defines this name at the top level.
+    Conflict(Ident),
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::Conflict(id) => write!(f, "{:?} is defined in more than one tree", id),
+        }
+    }
+}
+
+impl Error for LinkError {}
+
+/// Information about a function that is known without running it, the index of a
+/// [`HugFunction`] in [`HugTree::functions`] is the target of its [`HugValue::Function`].
+#[derive(Debug, Clone)]
+pub struct HugFunction {
+    pub function: Ident,
+    pub arity: usize,
+    /// Every argument in the order they were defined.
+    pub args: Vec<HugFunctionArgument>,
+}
+
+/// An argument of a [`HugFunction`].
+#[derive(Debug, Clone)]
+pub struct HugFunctionArgument {
+    pub name: Ident,
+    /// The value used when a call leaves this argument out.
+    pub default: Option<HugValue>,
+    /// Set for arguments after a `*` in the definition, these can only be passed by name
+    /// and not by position.
+    pub keyword_only: bool,
+}
+
+#[derive(Debug, Default)]
 pub struct HugTree {
-    pub entries: Vec<HugTreeEntry>,
+    root: HugScope,
+    /// Names that were referenced but not declared in any enclosing scope,
+    /// these are expected to be provided by another tree (e.g. an `@extern` module).
+    pub unresolved: Vec<Ident>,
+    pub functions: Vec<HugFunction>,
+    /// The target of every function definition, spanning the whole definition.
+    pub function_spans: Vec<Spanned<usize>>,
+    /// The target of every function whose body was skipped in
+    /// [outline mode](parser::ParserOptions::outline). The span is everything between the
+    /// braces, which can be parsed separately later.
+    pub skipped_bodies: Vec<Spanned<usize>>,
+    /// Warnings found while parsing, like uses of `@deprecated` functions.
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl HugTree {
     pub fn new() -> HugTree {
-        HugTree {
-            entries: Vec::new(),
+        HugTree::default()
+    }
+
+    pub fn merge_with(&mut self, mut other: HugTree) {
+        let offset = self.functions.len();
+        other.root.relocate(offset);
+        self.functions.extend(other.functions);
+        self.function_spans.extend(
+            other
+                .function_spans
+                .into_iter()
+                .map(|function| function.map(|target| target + offset)),
+        );
+        self.skipped_bodies.extend(
+            other
+                .skipped_bodies
+                .into_iter()
+                .map(|body| body.map(|target| target + offset)),
+        );
+        self.diagnostics.extend(other.diagnostics);
+
+        self.root.entries.extend(other.root.entries);
+        self.root.members.extend(other.root.members);
+        for id in other.root.idents {
+            self.root.declare(id);
+        }
+
+        for id in other.unresolved {
+            if !self.unresolved.contains(&id) {
+                self.unresolved.push(id);
+            }
         }
+        let root = &self.root;
+        self.unresolved.retain(|id| !root.is_declared(*id));
+    }
+
+    /// The top level scope, which holds every global definition.
+    pub fn root(&self) -> &HugScope {
+        &self.root
+    }
+
+    pub fn function_arity(&self, target: usize) -> Option<usize> {
+        self.functions.get(target).map(|f| f.arity)
+    }
+
+    /// Returns where the function at `target` is defined, in bytes from the start of the
+    /// script it was parsed from.
+    pub fn function_span(&self, target: usize) -> Option<Range<usize>> {
+        self.function_spans
+            .iter()
+            .find(|function| function.node == target)
+            .map(Spanned::span)
+    }
+
+    /// Checks if the function at `target` can be called with `arg_count` positional arguments
+    /// and nothing passed by name, see [`HugTree::check_named_call`].
+    pub fn check_call(&self, target: usize, arg_count: usize) -> Result<(), CallError> {
+        self.check_named_call(target, arg_count, &[])
     }
 
-    pub fn merge_with(&mut self, other: HugTree) {
-        self.entries.extend(other.entries.into_iter());
+    /// Checks if the function at `target` can be called with `arg_count` positional arguments
+    /// followed by the arguments `named`. Arguments with a default value may be left out, and
+    /// keyword-only arguments can only be passed by name.
+    pub fn check_named_call(
+        &self,
+        target: usize,
+        arg_count: usize,
+        named: &[Ident],
+    ) -> Result<(), CallError> {
+        let function = match self.functions.get(target) {
+            Some(function) => function,
+            None => return Ok(()),
+        };
+
+        let positional = function.args.iter().filter(|arg| !arg.keyword_only).count();
+        if arg_count > positional {
+            return Err(CallError::ArityMismatch {
+                expected: positional,
+                found: arg_count,
+            });
+        }
+
+        for (i, name) in named.iter().enumerate() {
+            let index = function
+                .args
+                .iter()
+                .position(|arg| arg.name == *name)
+                .ok_or(CallError::UnknownArgument(*name))?;
+            if index < arg_count || named[..i].contains(name) {
+                return Err(CallError::DuplicateArgument(*name));
+            }
+        }
+
+        let missing =
+            |arg: &&HugFunctionArgument| arg.default.is_none() && !named.contains(&arg.name);
+        let required = function.args[..positional]
+            .iter()
+            .filter(|arg| arg.default.is_none())
+            .count();
+        if let Some(arg) = function.args[arg_count..positional].iter().find(missing) {
+            return Err(if named.is_empty() {
+                CallError::ArityMismatch {
+                    expected: required,
+                    found: arg_count,
+                }
+            } else {
+                CallError::MissingArgument(arg.name)
+            });
+        }
+        match function.args[positional..].iter().find(missing) {
+            Some(arg) => Err(CallError::MissingArgument(arg.name)),
+            None => Ok(()),
+        }
     }
 
-    pub fn from_token_pairs(pairs: Vec<TokenPair>) -> HugTree {
+    pub fn from_token_pairs(pairs: Vec<TokenPair>) -> Result<HugTree, ParseError> {
         HugTreeParser::new(pairs).parse()
     }
 }
 
+/// Combines separately parsed trees into one program, in order. Function targets are
+/// relocated and the root scopes are merged, see [`HugTree::merge_with`]. Names used in one
+/// tree and defined in another are resolved. The trees have to be parsed with the same
+/// identifiers (see [`Tokenizer::with_idents`](hug_lexer::tokenizer::Tokenizer::with_idents)),
+/// and defining the same name at the top level of two trees is an error. Names brought in with
+/// `use` don't count, as they refer to the same thing in every tree.
+pub fn link(trees: Vec<HugTree>) -> Result<HugTree, LinkError> {
+    let mut linked = HugTree::new();
+    let mut defined = Vec::new();
+
+    for tree in trees {
+        let mut names = Vec::new();
+        for entry in tree.root.entries.iter() {
+            let name = match &entry.node {
+                HugTreeEntry::ModuleDefinition { module, .. }
+                | HugTreeEntry::ExternalModuleDefinition { module, .. } => *module,
+                HugTreeEntry::ExternalTypeDefinition { _type: name }
+                | HugTreeEntry::TypeDefinition { name, .. }
+                | HugTreeEntry::EnumDefinition { name, .. }
+                | HugTreeEntry::ExternalFunctionDefinition { function: name }
+                | HugTreeEntry::FunctionDefinition { function: name, .. }
+                | HugTreeEntry::VariableDefinition { variable: name, .. } => *name,
+                _ => continue,
+            };
+
+            if defined.contains(&name) {
+                return Err(LinkError::Conflict(name));
+            }
+            names.push(name);
+        }
+
+        defined.extend(names);
+        linked.merge_with(tree);
+    }
+
+    Ok(linked)
+}
+
 impl Display for HugTree {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut buffer = String::new();
-        for (i, entry) in self.entries.iter().enumerate() {
-            buffer.push_str(&format!("{:4}: {:?},\n", i, entry));
+        for (i, entry) in self.root.entries.iter().enumerate() {
+            buffer.push_str(&format!("{:4}: {:?},\n", i, entry.node));
         }
         write!(f, "[\n{}]", buffer)
     }
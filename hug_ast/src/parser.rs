@@ -1,19 +1,31 @@
-use std::{collections::HashMap, vec::IntoIter};
+use std::{collections::HashMap, error::Error, fmt::Display, ops::Range, vec::IntoIter};
 
 use hug_lexer::{
     parser::TokenPair,
     tokenizer::{AnnotationKind, KeywordKind, LiteralKind, TokenKind},
     FilterUseless,
 };
-use hug_lib::{value::HugValue, Ident};
+use hug_lib::{
+    error::{EvalError, ParseValueError},
+    span::Spanned,
+    value::{HugValue, TypeKind, TypedDefinition},
+    Ident,
+};
 
-use crate::{HugTree, HugTreeEntry, HugTreeFunctionCallArg};
+use crate::{
+    diagnostic::Diagnostic,
+    expression::{BinaryOperator, CallArg, Expression, UnaryOperator},
+    HugFunction, HugFunctionArgument, HugScope, HugTree, HugTreeEntry, Visibility,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct HugTreeAnnotationState {
     is_extern: bool,
     extern_location: String,
+    deprecated: Option<String>,
     custom: HashMap<Ident, HashMap<String, (LiteralKind, String)>>,
+    /// Set by a `public` or `private` keyword in front of the entry.
+    visibility: Visibility,
 }
 
 impl HugTreeAnnotationState {
@@ -21,19 +33,32 @@ impl HugTreeAnnotationState {
         HugTreeAnnotationState {
             is_extern: false,
             extern_location: String::new(),
+            deprecated: None,
             custom: HashMap::new(),
+            visibility: Visibility::Private,
         }
     }
 
     pub fn reset(&mut self) {
         self.is_extern = false;
         self.extern_location.clear();
+        self.deprecated = None;
         self.custom.clear();
+        self.visibility = Visibility::Private;
     }
 
-    #[inline]
-    pub fn push_custom(&mut self, key: Ident, value: HashMap<String, (LiteralKind, String)>) {
-        self.custom.insert(key, value).unwrap();
+    /// Stores the arguments of the custom annotation `key`. Using the same annotation twice on
+    /// one entry is an error, and the arguments of the first one are kept.
+    pub fn push_custom(
+        &mut self,
+        key: Ident,
+        value: HashMap<String, (LiteralKind, String)>,
+    ) -> Result<(), ParseError> {
+        if self.custom.contains_key(&key) {
+            return Err(ParseError::DuplicateAnnotation(key));
+        }
+        self.custom.insert(key, value);
+        Ok(())
     }
 
     #[inline]
@@ -41,6 +66,16 @@ impl HugTreeAnnotationState {
         self.custom.get(&key)
     }
 
+    /// Removes every custom annotation and returns them, for the entry they were put on.
+    pub fn take_custom(&mut self) -> HashMap<Ident, HashMap<String, (LiteralKind, String)>> {
+        std::mem::take(&mut self.custom)
+    }
+
+    /// Returns the visibility for the entry it was put on, and resets it to private.
+    pub fn take_visibility(&mut self) -> Visibility {
+        std::mem::take(&mut self.visibility)
+    }
+
     pub fn set_extern(&mut self, location: String) {
         self.is_extern = true;
         self.extern_location = location;
@@ -59,235 +94,1473 @@ impl HugTreeAnnotationState {
     }
 }
 
+/// Errors that stop the parser from producing a [`HugTree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The token stream ended while more tokens were expected.
+    UnexpectedEof,
+    UnexpectedToken(TokenPair),
+    /// A specific token was required, like the `{` after an `if` condition.
+    ExpectedToken {
+        expected: TokenKind,
+        found: TokenPair,
+    },
+    /// A type was required, like after the `:` of an argument or the `->` of a function.
+    ExpectedType {
+        found: TokenPair,
+    },
+    /// A name was used without being declared, see [`ParserOptions::deny_undefined_names`].
+    UndefinedName(Ident),
+    /// See [`ParserOptions::deny_unknown_annotations`].
+    UnknownAnnotation(Ident),
+    /// The same custom annotation was used twice on one entry.
+    DuplicateAnnotation(Ident),
+    /// A value was assigned to a name that wasn't declared with `let` (or otherwise) first.
+    UndeclaredAssignment(Ident),
+    /// See [`ParserOptions::deny_valued_if_without_else`].
+    ValuedIfWithoutElse,
+    /// The default value of an argument isn't a constant expression, or couldn't be computed.
+    InvalidDefault {
+        argument: Ident,
+        error: EvalError,
+    },
+    /// The discriminant of an enum variant isn't a constant expression, or couldn't be computed.
+    InvalidDiscriminant {
+        variant: Ident,
+        error: EvalError,
+    },
+    /// An argument without a default value comes after one that has one, which only
+    /// keyword-only arguments may do.
+    MissingDefault(Ident),
+    /// A variable was annotated with a type that isn't declared in the current scope or any
+    /// scope enclosing it.
+    UnknownType(Ident),
+    /// The value of a variable isn't a constant expression, or couldn't be computed.
+    InvalidInitializer {
+        variable: Ident,
+        error: EvalError,
+    },
+    /// A variable was initialized with a value of a different type than it was annotated with.
+    MismatchedType {
+        variable: Ident,
+        expected: TypeKind,
+        found: TypeKind,
+    },
+    /// A struct literal gives a field that its type doesn't have, or gives a field twice.
+    UnknownField {
+        type_name: Ident,
+        field: Ident,
+    },
+    /// A struct literal leaves out a field of its type.
+    MissingField {
+        type_name: Ident,
+        field: Ident,
+    },
+    /// A literal couldn't be read as the type it was given.
+    InvalidValue(ParseValueError),
+    /// Valid syntax for something the parser can't handle yet.
+    Unsupported(&'static str),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedEof => write!(f, "unexpected end of file"),
+            ParseError::UnexpectedToken(pair) => write!(
+                f,
+                "unexpected `{}` (at {}..{})",
+                pair.text, pair.span.start, pair.span.end
+            ),
+            ParseError::ExpectedToken { expected, found } => write!(
+                f,
+                "expected {:?}, found `{}` (at {}..{})",
+                expected, found.text, found.span.start, found.span.end
+            ),
+            ParseError::ExpectedType { found } => write!(
+                f,
+                "expected a type, found `{}` (at {}..{})",
+                found.text, found.span.start, found.span.end
+            ),
+            ParseError::UndefinedName(id) => write!(f, "{:?} is not defined", id),
+            ParseError::UnknownAnnotation(id) => write!(f, "unknown annotation {:?}", id),
+            ParseError::DuplicateAnnotation(id) => write!(f, "annotation {:?} is used twice", id),
+            ParseError::UndeclaredAssignment(id) => {
+                write!(f, "cannot assign to {:?}, it isn't declared", id)
+            }
+            ParseError::ValuedIfWithoutElse => {
+                write!(f, "an `if` with a value needs an `else`")
+            }
+            ParseError::InvalidDefault { argument, error } => {
+                write!(f, "invalid default value for {:?}: {}", argument, error)
+            }
+            ParseError::InvalidDiscriminant { variant, error } => {
+                write!(f, "invalid discriminant for {:?}: {}", variant, error)
+            }
+            ParseError::MissingDefault(id) => write!(
+                f,
+                "argument {:?} needs a default value, as an argument before it has one",
+                id
+            ),
+            ParseError::UnknownType(id) => write!(f, "{:?} is not a known type", id),
+            ParseError::InvalidInitializer { variable, error } => {
+                write!(f, "invalid value for {:?}: {}", variable, error)
+            }
+            ParseError::MismatchedType {
+                variable,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{:?} should be a {:?}, but its value is a {:?}",
+                variable, expected, found
+            ),
+            ParseError::UnknownField { type_name, field } => {
+                write!(
+                    f,
+                    "{:?} has no field {:?}, or it is given twice",
+                    type_name, field
+                )
+            }
+            ParseError::MissingField { type_name, field } => {
+                write!(f, "field {:?} of {:?} is missing", field, type_name)
+            }
+            ParseError::InvalidValue(e) => write!(f, "{}", e),
+            ParseError::Unsupported(what) => write!(f, "{} are not supported yet", what),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<ParseValueError> for ParseError {
+    fn from(e: ParseValueError) -> ParseError {
+        ParseError::InvalidValue(e)
+    }
+}
+
+/// Controls which conservative checks the parser performs.
+///
+/// `strict` turns on every check at once. Each individual flag overrides `strict` for its
+/// own check when set: `Some(true)` always enables it and `Some(false)` always disables it,
+/// while `None` (the default) follows `strict`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParserOptions {
+    pub strict: bool,
+    /// Reject names that aren't declared in the current scope or any scope enclosing it,
+    /// these are normally left for another tree to provide (see [`HugTree::unresolved`]).
+    pub deny_undefined_names: Option<bool>,
+    /// Reject annotations other than the built-in ones (like `@extern`).
+    pub deny_unknown_annotations: Option<bool>,
+    /// Reject an `if` without `else` whose branch might not evaluate to unit, as the
+    /// `if` evaluates to unit whenever the branch isn't taken.
+    pub deny_valued_if_without_else: Option<bool>,
+    /// Only parse the signatures of functions, for building an outline of a file quickly. Their
+    /// bodies are skipped and left empty, see [`HugTree::skipped_bodies`]. Names that are only
+    /// used inside of a skipped body don't end up in [`HugTree::unresolved`].
+    pub outline: bool,
+}
+
+impl ParserOptions {
+    pub fn strict() -> ParserOptions {
+        ParserOptions {
+            strict: true,
+            ..ParserOptions::default()
+        }
+    }
+
+    pub fn denies_undefined_names(&self) -> bool {
+        self.deny_undefined_names.unwrap_or(self.strict)
+    }
+
+    pub fn denies_unknown_annotations(&self) -> bool {
+        self.deny_unknown_annotations.unwrap_or(self.strict)
+    }
+
+    pub fn denies_valued_if_without_else(&self) -> bool {
+        self.deny_valued_if_without_else.unwrap_or(self.strict)
+    }
+}
+
 #[derive(Debug)]
 pub struct HugTreeParser {
-    tree: HugTree,
     pairs: IntoIter<TokenPair>,
+    options: ParserOptions,
     annotation_state: HugTreeAnnotationState,
+    /// Scopes that are currently being parsed, the root scope is always at the bottom.
+    scopes: Vec<HugScope>,
+    unresolved: Vec<Ident>,
+    functions: Vec<HugFunction>,
+    /// The field names of every type defined so far, which struct literals are checked against.
+    types: HashMap<Ident, Vec<Ident>>,
+    /// Functions marked with `@deprecated`, and the message to show when they are used.
+    deprecated: HashMap<Ident, String>,
+    /// Every name that was referenced, checked for deprecated functions once all of them are known.
+    references: Vec<Spanned<Ident>>,
+    function_spans: Vec<Spanned<usize>>,
+    skipped_bodies: Vec<Spanned<usize>>,
+    /// Span of the pair that was consumed last.
+    last_span: Range<usize>,
+    /// How many of the braces consumed so far weren't closed yet. Goes below zero for closing
+    /// braces of blocks whose opening brace wasn't parsed, see [`HugTreeParser::scope`].
+    brace_depth: isize,
+    /// Errors in statements inside blocks, which were skipped with [`HugTreeParser::recover`].
+    errors: Vec<Diagnostic>,
 }
 
 impl HugTreeParser {
     pub fn new(pairs: Vec<TokenPair>) -> HugTreeParser {
+        HugTreeParser::with_options(pairs, ParserOptions::default())
+    }
+
+    pub fn with_options(pairs: Vec<TokenPair>, options: ParserOptions) -> HugTreeParser {
         HugTreeParser {
+            options,
             annotation_state: HugTreeAnnotationState::new(),
             pairs: pairs.filter_useless().into_iter(),
-            tree: HugTree {
-                entries: Vec::new(),
-            },
+            scopes: vec![HugScope::new()],
+            unresolved: Vec::new(),
+            functions: Vec::new(),
+            types: HashMap::new(),
+            deprecated: HashMap::new(),
+            references: Vec::new(),
+            function_spans: Vec::new(),
+            skipped_bodies: Vec::new(),
+            last_span: 0..0,
+            brace_depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Declares `name` in the root scope with `value` before parsing, so names provided by the
+    /// host (like a builtin `print`) pass [`ParserOptions::deny_undefined_names`]. A name that
+    /// doesn't appear in the token pairs has no [`Ident`] yet and is skipped, as it can't be used.
+    pub fn predefine(&mut self, name: &str, value: HugValue) {
+        let id = self
+            .pairs
+            .as_slice()
+            .iter()
+            .filter(|pair| pair.text == name)
+            .find_map(|pair| pair.token.kind.expect_ident());
+
+        if let Some(id) = id {
+            let root = &mut self.scopes[0];
+            root.declare(id);
+            root.members.insert(id, value);
         }
     }
 
-    pub fn next(&mut self) -> Option<TokenPair> {
-        self.pairs.next()
+    /// Returns the next pair, or the null [`TokenPair`] when there are no pairs left.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> TokenPair {
+        let pair = self.pairs.next().unwrap_or_default();
+        if !pair.is_null() {
+            self.last_span = pair.span.clone();
+        }
+        match pair.token.kind {
+            TokenKind::OpenBrace => self.brace_depth += 1,
+            TokenKind::CloseBrace => self.brace_depth -= 1,
+            _ => (),
+        }
+        pair
     }
 
-    pub fn peek_next(&mut self) -> Option<TokenPair> {
-        self.pairs.clone().next()
+    /// Returns a copy of the next pair without consuming it, or the null [`TokenPair`] when there
+    /// are no pairs left. Only that one pair is cloned, not the rest of the stream.
+    pub fn peek_next(&mut self) -> TokenPair {
+        self.peek_nth(0)
     }
 
-    pub fn annotation(&mut self, kind: AnnotationKind) -> Option<HugTreeEntry> {
-        let mut vars: HashMap<String, (LiteralKind, String)> = HashMap::new();
+    /// Like [`HugTreeParser::peek_next`], but looks `n` pairs further ahead, so `peek_nth(0)`
+    /// is the next pair. Returns the null [`TokenPair`] past the end.
+    pub fn peek_nth(&self, n: usize) -> TokenPair {
+        self.pairs.as_slice().get(n).cloned().unwrap_or_default()
+    }
 
-        if self.peek_next().unwrap().token.kind == TokenKind::OpenParenthesis {
-            self.next(); // (
+    /// Like [`HugTreeParser::next`], but reaching the end of the file is an error.
+    pub fn expect_next(&mut self) -> Result<TokenPair, ParseError> {
+        let pair = self.next();
+        if pair.is_null() {
+            Err(ParseError::UnexpectedEof)
+        } else {
+            Ok(pair)
+        }
+    }
 
-            loop {
-                self.peek_next().unwrap().token.kind.expect_ident().unwrap();
-                let name = self.next().unwrap().text;
+    /// Like [`HugTreeParser::expect_next`], but the pair has to be an identifier.
+    fn expect_ident(&mut self) -> Result<Ident, ParseError> {
+        let pair = self.expect_next()?;
+        match pair.token.kind.expect_ident() {
+            Some(id) => Ok(id),
+            None => Err(ParseError::UnexpectedToken(pair)),
+        }
+    }
 
-                self.next()
-                    .unwrap()
-                    .token
-                    .kind
-                    .expect_kind(TokenKind::Assign)
-                    .unwrap();
+    fn expect_next_kind(&mut self, kind: TokenKind) -> Result<TokenPair, ParseError> {
+        let pair = self.expect_next()?;
+        if pair.token.kind == kind {
+            Ok(pair)
+        } else {
+            Err(ParseError::ExpectedToken {
+                expected: kind,
+                found: pair,
+            })
+        }
+    }
 
-                let value_pair = self.next().unwrap();
-                let value_kind = value_pair.token.kind.expect_literal().unwrap();
-                let value = value_pair.text;
-                let value = value[1..value.len() - 1].to_string();
+    #[inline]
+    fn current_scope(&mut self) -> &mut HugScope {
+        self.scopes.last_mut().unwrap()
+    }
 
-                vars.insert(name, (value_kind, value));
+    /// Checks if `id` is declared in the current scope or any scope enclosing it.
+    pub fn resolve(&self, id: Ident) -> bool {
+        self.scopes.iter().rev().any(|scope| scope.is_declared(id))
+    }
 
-                if self.next().unwrap().token.kind == TokenKind::CloseParenthesis {
-                    break;
+    /// Returns the value of `id` that is known while parsing, from the current scope or the
+    /// closest scope enclosing it that has one, see [`HugScope::lookup`].
+    pub fn lookup(&self, id: Ident) -> Option<&HugValue> {
+        self.scopes.iter().rev().find_map(|scope| scope.resolve(id))
+    }
+
+    /// Like [`HugScope::complete`], but includes the names of every scope enclosing the one
+    /// that is currently being parsed.
+    pub fn complete(&self, prefix: &str, idents: &HashMap<String, Ident>) -> Vec<Ident> {
+        crate::complete(prefix, idents, |id| self.resolve(id))
+    }
+
+    fn reference(&mut self, id: Ident) -> Result<(), ParseError> {
+        self.references
+            .push(Spanned::new(id, self.last_span.clone()));
+        if !self.resolve(id) {
+            if self.options.denies_undefined_names() {
+                return Err(ParseError::UndefinedName(id));
+            } else if !self.unresolved.contains(&id) {
+                self.unresolved.push(id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Declares every function, type and module in the upcoming scope before any of
+    /// it is parsed, so entries can refer to definitions that come after them.
+    fn declare_members(&mut self) {
+        let mut declarations = Vec::new();
+        let mut depth = 0usize;
+
+        let pairs = self.pairs.as_slice();
+        for (i, pair) in pairs.iter().enumerate() {
+            match pair.token.kind {
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace if depth == 0 => break,
+                TokenKind::CloseBrace => depth -= 1,
+                TokenKind::Keyword(
+                    KeywordKind::Function
+                    | KeywordKind::Type
+                    | KeywordKind::Enum
+                    | KeywordKind::Module,
+                ) if depth == 0 => {
+                    if let Some(id) = pairs.get(i + 1).and_then(|p| p.token.kind.expect_ident()) {
+                        declarations.push(id);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let scope = self.current_scope();
+        for id in declarations {
+            scope.declare(id);
+        }
+    }
+
+    /// Parses entries until the closing brace of the current block, the opening
+    /// brace should already be consumed.
+    pub fn scope(&mut self) -> Result<HugScope, ParseError> {
+        self.scopes.push(HugScope::new());
+        self.declare_members();
+        let depth = self.brace_depth;
+
+        loop {
+            let next = self.peek_next();
+            if next.is_null() {
+                return Err(ParseError::UnexpectedEof);
+            } else if next.token.kind == TokenKind::CloseBrace {
+                self.next(); // }
+                break;
+            }
+
+            self.annotation_state.reset();
+            match self.spanned_entry() {
+                Ok(Some(entry)) => self.current_scope().entries.push(entry),
+                Ok(None) => (),
+                Err(ParseError::UnexpectedEof) => return Err(ParseError::UnexpectedEof),
+                Err(e) => {
+                    let found = match &e {
+                        ParseError::UnexpectedToken(pair)
+                        | ParseError::ExpectedToken { found: pair, .. }
+                        | ParseError::ExpectedType { found: pair } => Some(pair),
+                        _ => None,
+                    };
+                    let span = found.map_or(self.last_span.clone(), |pair| pair.span.clone());
+                    self.errors
+                        .push(Diagnostic::error(e.to_string()).with_span(span));
+                    // The statement already consumed the closing brace of this block, so
+                    // recovering would skip past the end of the block
+                    if self.brace_depth < depth {
+                        break;
+                    }
+                    // Blocks the statement opened are skipped, so their closing braces
+                    // aren't mistaken for the end of this block
+                    while self.brace_depth > depth && !self.peek_next().is_null() {
+                        self.next();
+                    }
+                    self.recover();
                 }
             }
         }
 
+        Ok(self.scopes.pop().unwrap())
+    }
+
+    /// Parses the body of an `if`, `else` or `while`, which is either a block in braces or a
+    /// single statement after a colon, like `if c: x = 1`. The statement gets a scope of its
+    /// own just like a block, so a `let` in it isn't visible after it. Statements after a
+    /// colon are not recovered from, a body with an error is an error.
+    pub fn expect_scope_or_expression(&mut self) -> Result<HugScope, ParseError> {
+        let next = self.expect_next()?;
+        match next.token.kind {
+            TokenKind::OpenBrace => self.scope(),
+            TokenKind::Colon if self.peek_next().is_null() => Err(ParseError::UnexpectedEof),
+            TokenKind::Colon => {
+                self.scopes.push(HugScope::new());
+                self.annotation_state.reset();
+                let entry = self.spanned_entry();
+                let mut scope = self.scopes.pop().unwrap();
+                scope.entries.extend(entry?);
+                Ok(scope)
+            }
+            _ => Err(ParseError::ExpectedToken {
+                expected: TokenKind::OpenBrace,
+                found: next,
+            }),
+        }
+    }
+
+    /// Skips a block up to its closing brace by matching braces, without parsing it. The opening
+    /// brace should already be consumed. Returns the span between the braces.
+    fn skip_block(&mut self) -> Result<Range<usize>, ParseError> {
+        let start = self.last_span.end;
+        let mut depth = 0usize;
+        loop {
+            let pair = self.expect_next()?;
+            match pair.token.kind {
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace if depth == 0 => return Ok(start..pair.span.start),
+                TokenKind::CloseBrace => depth -= 1,
+                _ => (),
+            }
+        }
+    }
+
+    /// Skips the rest of a statement that failed to parse, up to the next keyword or
+    /// annotation that can start a new one or the closing brace of the current block.
+    /// Braces opened while skipping are matched, so blocks inside the statement are
+    /// skipped as a whole.
+    pub fn recover(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            let next = self.peek_next();
+            match next.token.kind {
+                _ if next.is_null() => break,
+                TokenKind::CloseBrace if depth == 0 => break,
+                TokenKind::Keyword(kind) if depth == 0 && kind.starts_statement() => break,
+                TokenKind::Annotation(_) if depth == 0 => break,
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace => depth -= 1,
+                _ => (),
+            }
+            self.next();
+        }
+    }
+
+    /// Errors in statements that were skipped so far, see [`HugTreeParser::recover`]. These
+    /// can be read before parsing is done, they end up in [`HugTree::diagnostics`] after.
+    pub fn errors(&self) -> &[Diagnostic] {
+        &self.errors
+    }
+
+    pub fn annotation(&mut self, kind: AnnotationKind) -> Result<Option<HugTreeEntry>, ParseError> {
+        if let AnnotationKind::Other(id) = kind {
+            if self.options.denies_unknown_annotations() {
+                return Err(ParseError::UnknownAnnotation(id));
+            }
+        }
+
+        if kind == AnnotationKind::Deprecated {
+            return self.deprecated_annotation();
+        }
+
+        let mut vars = self.annotation_arguments()?;
+
         if vars.keys().len() > 0 {
             match kind {
-                AnnotationKind::Extern => self
-                    .annotation_state
-                    .set_extern(vars.remove("location").unwrap().1),
-                AnnotationKind::Other(id) => self.annotation_state.push_custom(id, vars),
+                AnnotationKind::Extern => self.annotation_state.set_extern(
+                    vars.remove("location")
+                        .map(|(_, location)| location)
+                        .unwrap_or_default(),
+                ),
+                AnnotationKind::Other(id) => self.annotation_state.push_custom(id, vars)?,
+                AnnotationKind::Deprecated => unreachable!(),
             }
         } else {
             match kind {
                 AnnotationKind::Extern => self.annotation_state.set_extern("".to_string()),
-                AnnotationKind::Other(id) => self.annotation_state.push_custom(id, vars),
+                AnnotationKind::Other(id) => self.annotation_state.push_custom(id, vars)?,
+                AnnotationKind::Deprecated => unreachable!(),
+            }
+        }
+
+        self.annotated_entry()
+    }
+
+    /// Parses what an annotation applies to. An annotation isn't an AST entry by itself, it
+    /// supports the following entry, or every entry of a `{ .. }` block right after it. The
+    /// entries of such a block are added to the current scope as they are parsed, and `None`
+    /// is returned.
+    fn annotated_entry(&mut self) -> Result<Option<HugTreeEntry>, ParseError> {
+        if self.peek_next().token.kind != TokenKind::OpenBrace {
+            return self.next_entry();
+        }
+        self.next(); // {
+
+        let state = self.annotation_state.clone();
+        loop {
+            let next = self.peek_next();
+            if next.is_null() {
+                return Err(ParseError::UnexpectedEof);
+            } else if next.token.kind == TokenKind::CloseBrace {
+                self.next(); // }
+                break;
+            }
+
+            self.annotation_state = state.clone();
+            if let Some(entry) = self.spanned_entry()? {
+                self.current_scope().entries.push(entry);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Parses the optional `(name = value, ..)` after an annotation. String values are stored
+    /// without their quotes, other literals are stored as they were written.
+    pub fn annotation_arguments(
+        &mut self,
+    ) -> Result<HashMap<String, (LiteralKind, String)>, ParseError> {
+        let mut vars = HashMap::new();
+
+        if self.peek_next().token.kind == TokenKind::OpenParenthesis {
+            self.next(); // (
+
+            loop {
+                let name = self.expect_next()?;
+                if name.token.kind.expect_ident().is_none() {
+                    return Err(ParseError::UnexpectedToken(name));
+                }
+                let name = name.node.text;
+
+                self.expect_next_kind(TokenKind::Assign)?;
+
+                let value_pair = self.expect_next()?;
+                let value_kind = match value_pair.token.kind.expect_literal() {
+                    Some(kind) => kind,
+                    None => return Err(ParseError::UnexpectedToken(value_pair)),
+                };
+                let value = match value_kind {
+                    LiteralKind::String => {
+                        value_pair.text[1..value_pair.text.len() - 1].to_string()
+                    }
+                    _ => value_pair.node.text,
+                };
+
+                vars.insert(name, (value_kind, value));
+
+                if self.expect_next()?.token.kind == TokenKind::CloseParenthesis {
+                    break;
+                }
+            }
+        }
+
+        Ok(vars)
+    }
+
+    /// Parses `@deprecated` with an optional message, like `@deprecated("use bar instead")`.
+    fn deprecated_annotation(&mut self) -> Result<Option<HugTreeEntry>, ParseError> {
+        let mut message = String::from("this function is deprecated");
+
+        if self.peek_next().token.kind == TokenKind::OpenParenthesis {
+            self.next(); // (
+
+            let pair = self.expect_next_kind(TokenKind::Literal(LiteralKind::String))?;
+            match pair.parse_literal() {
+                Some(HugValue::String(text)) => message = text,
+                _ => return Err(ParseError::UnexpectedToken(pair)),
             }
+
+            self.expect_next_kind(TokenKind::CloseParenthesis)?;
         }
 
-        self.next_entry() // An annotation isn't an AST entry by itself, it supports the following entry
+        self.annotation_state.deprecated = Some(message);
+        self.annotated_entry()
     }
 
-    pub fn keyword(&mut self, kind: KeywordKind) -> Option<HugTreeEntry> {
-        match kind {
-            // KeywordKind::Enum => todo!(),
+    pub fn keyword(&mut self, kind: KeywordKind) -> Result<Option<HugTreeEntry>, ParseError> {
+        Ok(match kind {
+            KeywordKind::Enum => {
+                let visibility = self.annotation_state.take_visibility();
+                let name = self.expect_ident()?;
+                self.current_scope().declare(name);
+                let variants = self.enum_variants()?;
+                Some(HugTreeEntry::EnumDefinition {
+                    name,
+                    variants,
+                    visibility,
+                })
+            }
             KeywordKind::Function => {
+                let start = self.last_span.start;
+                let function = self.expect_ident()?;
+                self.current_scope().declare(function);
+
+                if let Some(message) = self.annotation_state.deprecated.take() {
+                    self.deprecated.insert(function, message);
+                }
+
                 if self.annotation_state.is_extern {
-                    Some(HugTreeEntry::ExternalFunctionDefinition {
-                        function: self.next().unwrap().token.kind.expect_ident().unwrap(),
-                    })
+                    Some(HugTreeEntry::ExternalFunctionDefinition { function })
                 } else {
-                    todo!()
+                    // Taken first, as functions nested in the body are added while parsing it
+                    let target = self.functions.len();
+                    let definition = self.function_definition(function)?;
+                    self.function_spans
+                        .push(Spanned::new(target, start..self.last_span.end));
+                    Some(definition)
                 }
             }
-            KeywordKind::Let => Some(self.variable_definition()),
+            KeywordKind::If => Some(HugTreeEntry::Expression(self.if_expression()?)),
+            KeywordKind::TypeOf => {
+                let expression = self.type_of()?;
+                Some(HugTreeEntry::Expression(
+                    self.continue_expression(expression)?,
+                ))
+            }
+            KeywordKind::Let => Some(self.variable_definition()?),
+            KeywordKind::While => {
+                let condition = self.expression()?;
+                Some(HugTreeEntry::While {
+                    condition,
+                    body: self.expect_scope_or_expression()?,
+                })
+            }
             KeywordKind::Module => {
                 if let Some(location) = self.annotation_state.get_extern() {
-                    Some(HugTreeEntry::ExternalModuleDefinition {
-                        location,
-                        module: self.next().unwrap().token.kind.expect_ident().unwrap(),
-                    })
+                    let module = self.expect_ident()?;
+                    self.current_scope().declare(module);
+                    Some(HugTreeEntry::ExternalModuleDefinition { location, module })
                 } else {
-                    todo!() // TODO: Non-@extern modules not implemented yet.
+                    let module = self.expect_ident()?;
+                    self.current_scope().declare(module);
+                    self.expect_next_kind(TokenKind::OpenBrace)?;
+                    Some(HugTreeEntry::ModuleDefinition {
+                        module,
+                        body: self.scope()?,
+                    })
                 }
             }
-            // TODO: KeywordKind::Private => todo!(),
-            // TODO: KeywordKind::Public => todo!(),
+            KeywordKind::Private | KeywordKind::Public => {
+                // Like an annotation, a visibility modifier applies to the following entry
+                self.annotation_state.visibility = if kind == KeywordKind::Public {
+                    Visibility::Public
+                } else {
+                    Visibility::Private
+                };
+                return self.next_entry();
+            }
             KeywordKind::Type => {
                 if self.annotation_state.is_extern {
-                    Some(HugTreeEntry::ExternalTypeDefinition {
-                        _type: self.next().unwrap().token.kind.expect_ident().unwrap(),
-                    })
+                    let _type = self.expect_ident()?;
+                    self.current_scope().declare(_type);
+                    Some(HugTreeEntry::ExternalTypeDefinition { _type })
                 } else {
-                    todo!() // TODO: Write non-extern type
+                    let visibility = self.annotation_state.take_visibility();
+                    let name = self.expect_ident()?;
+                    self.current_scope().declare(name);
+                    let fields = self.type_fields()?;
+                    self.types
+                        .insert(name, fields.iter().map(|(field, _)| *field).collect());
+                    Some(HugTreeEntry::TypeDefinition {
+                        name,
+                        fields,
+                        visibility,
+                    })
                 }
             }
-            // KeywordKind::Use => todo!(),
+            KeywordKind::Return => {
+                let next = self.peek_next();
+                let value = match next.token.kind {
+                    _ if next.is_null() => None,
+                    TokenKind::CloseBrace => None,
+                    TokenKind::Keyword(kind) if kind.starts_statement() => None,
+                    _ => Some(self.expression()?),
+                };
+                Some(HugTreeEntry::Return(value))
+            }
+            KeywordKind::Use => {
+                let mut path = vec![self.expect_ident()?];
+                self.reference(path[0])?;
+                while self.peek_next().token.kind == TokenKind::Dot {
+                    self.next(); // .
+                    path.push(self.expect_ident()?);
+                }
+
+                self.current_scope().declare(*path.last().unwrap());
+                Some(HugTreeEntry::Use { path })
+            }
             _ => None,
-        }
+        })
     }
 
-    pub fn identifier(&mut self, id: Ident) -> HugTreeEntry {
-        let next = self.next().unwrap();
-        match next.token.kind {
-            TokenKind::Dot => {
-                // TODO: Accessing fields
-                todo!()
-            }
-            TokenKind::OpenParenthesis => {
-                // TODO: Calling functions
-                let mut args = Vec::new();
-                loop {
-                    let _next = self.next().unwrap();
-                    if let Some(value) = _next.parse_literal() {
-                        args.push(HugTreeFunctionCallArg::Value(value));
-                    } else if let Some(value) = _next.token.kind.expect_ident() {
-                        args.push(HugTreeFunctionCallArg::Variable(value));
-                    } else if let TokenKind::CloseParenthesis = _next.token.kind {
-                        break;
+    pub fn function_definition(&mut self, function: Ident) -> Result<HugTreeEntry, ParseError> {
+        // Taken before the body resets the annotation state for its own entries
+        let visibility = self.annotation_state.take_visibility();
+        self.expect_next_kind(TokenKind::OpenParenthesis)?;
+
+        let mut args = Vec::new();
+        let mut arg_types = Vec::new();
+        let mut arguments: Vec<HugFunctionArgument> = Vec::new();
+        // Where the `*` separating keyword-only arguments is, and the pair itself
+        let mut separator = None;
+        loop {
+            let next = self.expect_next()?;
+            match next.token.kind {
+                TokenKind::Multiply if separator.is_none() => separator = Some((args.len(), next)),
+                TokenKind::Identifier(arg) => {
+                    args.push(arg);
+                    arg_types.push(if self.peek_next().token.kind == TokenKind::Colon {
+                        self.next(); // :
+                        Some(self.type_name()?)
+                    } else {
+                        None
+                    });
+                    let default = if self.peek_next().token.kind == TokenKind::Assign {
+                        self.next(); // =
+                        let value = self.expression()?.get_constant_value().map_err(|error| {
+                            ParseError::InvalidDefault {
+                                argument: arg,
+                                error,
+                            }
+                        })?;
+                        Some(value)
+                    } else {
+                        None
+                    };
+                    // Keyword-only arguments are passed by name, so any of them can leave out
+                    // a default
+                    let keyword_only = separator.is_some();
+                    if default.is_none()
+                        && !keyword_only
+                        && arguments.iter().any(|arg| arg.default.is_some())
+                    {
+                        return Err(ParseError::MissingDefault(arg));
                     }
+                    arguments.push(HugFunctionArgument {
+                        name: arg,
+                        default,
+                        keyword_only,
+                    });
                 }
-
-                HugTreeEntry::FunctionCall { function: id, args }
+                TokenKind::Comma => (),
+                TokenKind::CloseParenthesis => break,
+                _ => return Err(ParseError::UnexpectedToken(next)),
             }
-            TokenKind::Assign => {
-                // TODO: Assigning values to existing variables
-                todo!()
+        }
+
+        if let Some((position, pair)) = separator {
+            if position == args.len() {
+                return Err(ParseError::UnexpectedToken(pair));
             }
-            _ => panic!("Unexpected token after identifier: {:?}", next),
         }
+
+        let return_type = if self.peek_next().token.kind == TokenKind::Arrow {
+            self.next(); // ->
+            Some(self.type_name()?)
+        } else {
+            None
+        };
+
+        self.expect_next_kind(TokenKind::OpenBrace)?;
+
+        let target = self.functions.len();
+        self.functions.push(HugFunction {
+            function,
+            arity: args.len(),
+            args: arguments,
+        });
+        self.current_scope()
+            .members
+            .insert(function, HugValue::Function(target));
+
+        let body = if self.options.outline {
+            let span = self.skip_block()?;
+            self.skipped_bodies.push(Spanned::new(target, span));
+            Ok(HugScope::new())
+        } else {
+            // Arguments are only visible inside of the function body
+            self.scopes.push(HugScope {
+                idents: args.clone(),
+                ..HugScope::new()
+            });
+            let body = self.scope();
+            self.scopes.pop();
+            body
+        };
+
+        Ok(HugTreeEntry::FunctionDefinition {
+            function,
+            args,
+            arg_types,
+            return_type,
+            body: body?,
+            visibility,
+        })
     }
 
-    pub fn variable_definition(&mut self) -> HugTreeEntry {
-        let name = self.next().unwrap();
-        let name = name.token.kind.expect_ident().unwrap();
+    pub fn identifier(&mut self, id: Ident) -> Result<HugTreeEntry, ParseError> {
+        match self.peek_next().token.kind {
+            TokenKind::OpenParenthesis => {
+                self.reference(id)?;
+                self.next(); // (
+                let args = self.call_arguments()?;
 
-        let next = self.next().unwrap();
-        match next.token.kind {
+                let next = self.peek_next().token.kind;
+                if matches!(
+                    next,
+                    TokenKind::Dot | TokenKind::OptionalDot | TokenKind::OpenBracket
+                ) || BinaryOperator::from_token(next).is_some()
+                {
+                    let call = Expression::Call { function: id, args };
+                    Ok(HugTreeEntry::Expression(self.continue_expression(call)?))
+                } else {
+                    Ok(HugTreeEntry::FunctionCall { function: id, args })
+                }
+            }
             TokenKind::Assign => {
-                let value = self.next().unwrap();
-                let value = value.parse_literal().unwrap();
-                HugTreeEntry::VariableDefinition {
-                    variable: name,
+                self.next(); // =
+                if !self.resolve(id) {
+                    return Err(ParseError::UndeclaredAssignment(id));
+                }
+
+                let value = self.expression()?;
+                Ok(HugTreeEntry::Assignment {
+                    variable: id,
                     value,
+                })
+            }
+            // `x += 1` is the same as `x = x + 1`
+            kind if BinaryOperator::from_compound_assignment(kind).is_some() => {
+                let variable = Spanned::new(Expression::Variable(id), self.last_span.clone());
+                self.next(); // +=, -=, *=, /= or %=
+                if !self.resolve(id) {
+                    return Err(ParseError::UndeclaredAssignment(id));
                 }
+                self.reference(id)?;
+
+                let value = Expression::BinaryOp {
+                    op: BinaryOperator::from_compound_assignment(kind).unwrap(),
+                    lhs: Box::new(variable),
+                    rhs: self.operand_expression()?,
+                };
+                Ok(HugTreeEntry::Assignment {
+                    variable: id,
+                    value: value.fold_constants(),
+                })
+            }
+            _ => {
+                self.reference(id)?;
+                let expression = self.continue_expression(Expression::Variable(id))?;
+                Ok(HugTreeEntry::Expression(expression))
             }
-            TokenKind::Colon => todo!(),
-            _ => panic!("Unexpected token at variable definition: {:?}", next),
         }
     }
 
-    pub fn next_entry(&mut self) -> Option<HugTreeEntry> {
-        if let Some(pair) = self.next() {
-            match pair.token.kind {
-                // TokenKind::Literal(_) => todo!(),
-                TokenKind::Keyword(kind) => self.keyword(kind),
-                TokenKind::Identifier(id) => Some(self.identifier(id)),
-                TokenKind::Annotation(kind) => self.annotation(kind),
-                // TokenKind::Dot => todo!(),
-                // TokenKind::OpenParenthesis => todo!(),
-                // TokenKind::CloseParenthesis => todo!(),
-                // TokenKind::OpenBrace => todo!(),
-                // TokenKind::CloseBrace => todo!(),
-                // TokenKind::OpenBracket => todo!(),
-                // TokenKind::CloseBracket => todo!(),
-                // TokenKind::Colon => todo!(),
-                // TokenKind::Assign => todo!(),
-                // TokenKind::Add => todo!(),
-                // TokenKind::Subtract => todo!(),
-                // TokenKind::Multiply => todo!(),
-                // TokenKind::Divide => todo!(),
-                // TokenKind::Modulus => todo!(),
-                // TokenKind::AddAssign => todo!(),
-                // TokenKind::SubtractAssign => todo!(),
-                // TokenKind::MultiplyAssign => todo!(),
-                // TokenKind::DivideAssign => todo!(),
-                // TokenKind::ModulusAssign => todo!(),
-                // TokenKind::Not => todo!(),
-                // TokenKind::And => todo!(),
-                // TokenKind::Or => todo!(),
-                // TokenKind::IsEqualTo => todo!(),
-                // TokenKind::IsNotEqualTo => todo!(),
-                // TokenKind::LessThan => todo!(),
-                // TokenKind::GreaterThan => todo!(),
-                // TokenKind::LessThanOrEquals => todo!(),
-                // TokenKind::GreaterThanOrEquals => todo!(),
-                // TokenKind::BinaryAnd => todo!(),
-                // TokenKind::BinaryOr => todo!(),
-                // TokenKind::BinaryNot => todo!(),
-                // TokenKind::BinaryXOr => todo!(),
-                // TokenKind::BinaryAndAssign => todo!(),
-                // TokenKind::BinaryOrAssign => todo!(),
-                // TokenKind::BinaryNotAssign => todo!(),
-                // TokenKind::BinaryXOrAssign => todo!(),
-                // TokenKind::ShiftLeft => todo!(),
-                // TokenKind::ShiftRight => todo!(),
-                // TokenKind::ShiftLeftOverflow => todo!(),
-                // TokenKind::ShiftRightOverflow => todo!(),
-                TokenKind::Unknown => panic!("Unknown token: {}!", pair.text),
-                _ => self.next_entry(),
-                // _ => unreachable!(),
+    /// Parses the arguments of a function call, the opening parenthesis should already be consumed.
+    pub fn call_arguments(&mut self) -> Result<Vec<CallArg>, ParseError> {
+        let mut args = Vec::new();
+        if self.peek_next().token.kind == TokenKind::CloseParenthesis {
+            self.next(); // )
+            return Ok(args);
+        }
+
+        loop {
+            let next = self.peek_next();
+            if next.token.kind == TokenKind::Ellipsis {
+                self.next(); // ...
+                args.push(CallArg::Spread(self.expression()?));
+            } else if let (Some(name), TokenKind::Assign) =
+                (next.token.kind.expect_ident(), self.peek_nth(1).token.kind)
+            {
+                self.next(); // name
+                self.next(); // =
+                args.push(CallArg::Named(name, self.expression()?));
+            } else if args.iter().any(|arg: &CallArg| arg.name().is_some()) {
+                // Positional arguments can't come after named ones
+                return Err(ParseError::UnexpectedToken(next));
+            } else {
+                args.push(CallArg::Positional(self.expression()?));
+            }
+
+            let next = self.expect_next()?;
+            match next.token.kind {
+                TokenKind::Comma => (),
+                TokenKind::CloseParenthesis => break,
+                _ => return Err(ParseError::UnexpectedToken(next)),
+            }
+        }
+
+        Ok(args)
+    }
+
+    pub fn expression(&mut self) -> Result<Expression, ParseError> {
+        let pair = self.expect_next()?;
+        self.expression_from(pair)
+    }
+
+    /// Parses an expression to be the operand of another one, along with its span.
+    fn operand_expression(&mut self) -> Result<Box<Spanned<Expression>>, ParseError> {
+        let start = self.peek_next().span.start;
+        let expression = self.expression()?;
+        Ok(self.spanned(expression, start))
+    }
+
+    /// Wraps `expression`, which starts at `start` and ends with the pair consumed last.
+    fn spanned(&self, expression: Expression, start: usize) -> Box<Spanned<Expression>> {
+        Box::new(Spanned::new(expression, start..self.last_span.end))
+    }
+
+    /// Parses an expression that starts with the already consumed `pair`.
+    fn expression_from(&mut self, pair: TokenPair) -> Result<Expression, ParseError> {
+        let start = pair.span.start;
+        let operand = self.operand(pair)?;
+        self.binary(operand, start, 0)
+    }
+
+    /// Parses the rest of an expression that starts with the already parsed `base`, which
+    /// has to be the pair consumed last.
+    fn continue_expression(&mut self, base: Expression) -> Result<Expression, ParseError> {
+        let start = self.last_span.start;
+        let operand = self.postfix(base, start)?;
+        self.binary(operand, start, 0)
+    }
+
+    /// Parses everything that can be on either side of a binary operator, including any
+    /// number of prefix operators, like `-` in `-x.y` which negates `x.y`.
+    fn operand(&mut self, pair: TokenPair) -> Result<Expression, ParseError> {
+        if let Some(op) = UnaryOperator::from_token(pair.token.kind) {
+            let pair = self.expect_next()?;
+            let start = pair.span.start;
+            let operand = self.operand(pair)?;
+            return Ok(Expression::UnaryOp {
+                op,
+                operand: self.spanned(operand, start),
+            });
+        }
+
+        let start = pair.span.start;
+        let expression = self.primary_expression(pair)?;
+        self.postfix(expression, start)
+    }
+
+    /// Parses binary operators after `lhs`, which starts at `start`, using precedence
+    /// climbing. Only operators that bind at least as tightly as `min_precedence` are parsed.
+    fn binary(
+        &mut self,
+        mut lhs: Expression,
+        start: usize,
+        min_precedence: u8,
+    ) -> Result<Expression, ParseError> {
+        while let Some(op) = BinaryOperator::from_token(self.peek_next().token.kind) {
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            let lhs_span = start..self.last_span.end;
+            self.next(); // op
+
+            let pair = self.expect_next()?;
+            let rhs_start = pair.span.start;
+            let mut rhs = self.operand(pair)?;
+
+            // Operators that bind more tightly take the right hand side as their left hand side
+            while let Some(next) = BinaryOperator::from_token(self.peek_next().token.kind) {
+                if next.precedence() > precedence {
+                    rhs = self.binary(rhs, rhs_start, precedence + 1)?;
+                } else {
+                    break;
+                }
+            }
+
+            lhs = Expression::BinaryOp {
+                op,
+                lhs: Box::new(Spanned::new(lhs, lhs_span)),
+                rhs: self.spanned(rhs, rhs_start),
+            }
+            .fold_constants();
+        }
+
+        Ok(lhs)
+    }
+
+    fn primary_expression(&mut self, pair: TokenPair) -> Result<Expression, ParseError> {
+        match pair.token.kind {
+            TokenKind::Literal(_) => match pair.parse_literal() {
+                Some(value) => Ok(Expression::Literal(value)),
+                None => Err(ParseError::UnexpectedToken(pair)),
+            },
+            TokenKind::Identifier(id) => {
+                self.reference(id)?;
+                if self.peek_next().token.kind == TokenKind::OpenParenthesis {
+                    self.next(); // (
+                    Ok(Expression::Call {
+                        function: id,
+                        args: self.call_arguments()?,
+                    })
+                } else if self.peek_next().token.kind == TokenKind::OpenBrace
+                    && self.types.contains_key(&id)
+                {
+                    self.next(); // {
+                    self.struct_literal(id)
+                } else {
+                    Ok(Expression::Variable(id))
+                }
+            }
+            TokenKind::OpenParenthesis => {
+                let expression = self.expression()?;
+                self.expect_next_kind(TokenKind::CloseParenthesis)?;
+                Ok(expression)
+            }
+            TokenKind::OpenBracket => self.array_literal(),
+            TokenKind::Keyword(KeywordKind::If) => self.if_expression(),
+            TokenKind::Keyword(KeywordKind::TypeOf) => self.type_of(),
+            _ => Err(ParseError::UnexpectedToken(pair)),
+        }
+    }
+
+    /// Parses the elements of an array literal, the opening bracket should already be consumed.
+    /// The last element may be followed by a comma.
+    fn array_literal(&mut self) -> Result<Expression, ParseError> {
+        let mut elements = Vec::new();
+        loop {
+            if self.peek_next().token.kind == TokenKind::CloseBracket {
+                self.next(); // ]
+                break;
+            }
+
+            elements.push(self.expression()?);
+
+            let next = self.expect_next()?;
+            match next.token.kind {
+                TokenKind::Comma => (),
+                TokenKind::CloseBracket => break,
+                _ => return Err(ParseError::UnexpectedToken(next)),
+            }
+        }
+
+        Ok(Expression::ArrayLiteral(elements))
+    }
+
+    /// Parses the `x: 1, y: 2 }` of a struct literal after `Point {`. Every field of the type
+    /// has to be given exactly once, in any order. The last field may be followed by a comma.
+    fn struct_literal(&mut self, type_name: Ident) -> Result<Expression, ParseError> {
+        let mut fields = Vec::new();
+        loop {
+            if self.peek_next().token.kind == TokenKind::CloseBrace {
+                self.next(); // }
+                break;
+            }
+
+            let field = self.expect_ident()?;
+            if !self.types[&type_name].contains(&field)
+                || fields.iter().any(|(given, _)| *given == field)
+            {
+                return Err(ParseError::UnknownField { type_name, field });
+            }
+            self.expect_next_kind(TokenKind::Colon)?;
+            fields.push((field, self.expression()?));
+
+            let next = self.expect_next()?;
+            match next.token.kind {
+                TokenKind::Comma => (),
+                TokenKind::CloseBrace => break,
+                _ => return Err(ParseError::UnexpectedToken(next)),
+            }
+        }
+
+        if let Some(field) = self.types[&type_name]
+            .iter()
+            .find(|field| !fields.iter().any(|(given, _)| given == *field))
+        {
+            return Err(ParseError::MissingField {
+                type_name,
+                field: *field,
+            });
+        }
+
+        Ok(Expression::StructLiteral { type_name, fields })
+    }
+
+    /// Parses the operand of `typeof`, which should already be consumed. Like a prefix
+    /// operator it binds more tightly than binary operators, so `typeof a + b` is
+    /// `(typeof a) + b`.
+    fn type_of(&mut self) -> Result<Expression, ParseError> {
+        let pair = self.expect_next()?;
+        let start = pair.span.start;
+        let operand = self.operand(pair)?;
+        Ok(Expression::TypeOf(self.spanned(operand, start)))
+    }
+
+    /// Parses any number of `.field`, `.method(args)` and `[index]` after `base`, which
+    /// starts at `start`.
+    fn postfix(&mut self, mut base: Expression, start: usize) -> Result<Expression, ParseError> {
+        while matches!(
+            self.peek_next().token.kind,
+            TokenKind::Dot | TokenKind::OptionalDot | TokenKind::OpenBracket
+        ) {
+            let base_span = start..self.last_span.end;
+            // ., ?. or [
+            let kind = self.next().token.kind;
+            if kind == TokenKind::OpenBracket {
+                let index = self.operand_expression()?;
+                self.expect_next_kind(TokenKind::CloseBracket)?;
+                base = Expression::Index {
+                    base: Box::new(Spanned::new(base, base_span)),
+                    index,
+                };
+                continue;
+            }
+            let optional = kind == TokenKind::OptionalDot;
+
+            let pair = self.expect_next()?;
+            let field = match pair.token.kind.expect_ident() {
+                Some(field) => field,
+                None => return Err(ParseError::UnexpectedToken(pair)),
+            };
+
+            base = if optional {
+                Expression::OptionalFieldAccess {
+                    base: Box::new(Spanned::new(base, base_span)),
+                    field,
+                }
+            } else if self.peek_next().token.kind == TokenKind::OpenParenthesis {
+                self.next(); // (
+                Expression::MethodCall {
+                    base: Box::new(Spanned::new(base, base_span)),
+                    method: field,
+                    args: self.call_arguments()?,
+                }
+            } else {
+                Expression::FieldAccess {
+                    base: Box::new(Spanned::new(base, base_span)),
+                    field,
+                }
+            };
+        }
+
+        Ok(base)
+    }
+
+    /// Parses `if condition { .. }` with an optional `else { .. }` or `else if ..`, the `if`
+    /// should already be consumed. An `else if` becomes an `else` branch that only holds
+    /// the inner `if`. Either branch can also be a single statement after a colon, see
+    /// [`HugTreeParser::expect_scope_or_expression`].
+    pub fn if_expression(&mut self) -> Result<Expression, ParseError> {
+        let condition = self.operand_expression()?;
+
+        let then_branch = self.expect_scope_or_expression()?;
+
+        let else_branch = if self.peek_next().token.kind == TokenKind::Keyword(KeywordKind::Else) {
+            self.next(); // else
+            if self.peek_next().token.kind == TokenKind::Keyword(KeywordKind::If) {
+                let start = self.next().span.start; // if
+                let mut branch = HugScope::new();
+                let inner = HugTreeEntry::Expression(self.if_expression()?);
+                branch
+                    .entries
+                    .push(Spanned::new(inner, start..self.last_span.end));
+                Some(branch)
+            } else {
+                Some(self.expect_scope_or_expression()?)
             }
         } else {
-            self.next_entry()
+            if self.options.denies_valued_if_without_else() && !then_branch.yields_unit() {
+                return Err(ParseError::ValuedIfWithoutElse);
+            }
+            None
+        };
+
+        Ok(Expression::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    /// Parses a single expression that should make up all of `pairs`.
+    pub fn parse_expression_only(pairs: Vec<TokenPair>) -> Result<Expression, ParseError> {
+        let mut parser = HugTreeParser::new(pairs);
+        let expression = parser.expression()?;
+
+        let next = parser.next();
+        if next.is_null() {
+            Ok(expression)
+        } else {
+            Err(ParseError::UnexpectedToken(next))
+        }
+    }
+
+    /// Parses the name of a type, like the `Int32` in `let x: Int32 = 5`. Any identifier
+    /// that isn't a built-in type is read as a [`TypeKind::Other`].
+    fn type_name(&mut self) -> Result<TypeKind, ParseError> {
+        let type_pair = self.expect_next()?;
+        let _type = match type_pair.expect_type() {
+            Some(_type) => _type,
+            None => return Err(ParseError::ExpectedType { found: type_pair }),
+        };
+        if let TypeKind::Other(id) = _type {
+            self.reference(id)?;
+        }
+        Ok(_type)
+    }
+
+    /// Parses the fields of a type definition, like the `{ x: Int32, y: Int32 }` after
+    /// `type Point`. The last field may be followed by a comma.
+    fn type_fields(&mut self) -> Result<Vec<(Ident, TypeKind)>, ParseError> {
+        self.expect_next_kind(TokenKind::OpenBrace)?;
+
+        let mut fields = Vec::new();
+        loop {
+            if self.peek_next().token.kind == TokenKind::CloseBrace {
+                self.next(); // }
+                break;
+            }
+
+            let name = self.expect_ident()?;
+            self.expect_next_kind(TokenKind::Colon)?;
+            fields.push((name, self.type_name()?));
+
+            let next = self.expect_next()?;
+            match next.token.kind {
+                TokenKind::Comma => (),
+                TokenKind::CloseBrace => break,
+                _ => return Err(ParseError::UnexpectedToken(next)),
+            }
+        }
+
+        Ok(fields)
+    }
+
+    /// Parses the `{ Variant, Other = value, .. }` after `enum name`, the discriminants have
+    /// to be constant expressions.
+    fn enum_variants(&mut self) -> Result<Vec<(Ident, Option<HugValue>)>, ParseError> {
+        self.expect_next_kind(TokenKind::OpenBrace)?;
+
+        let mut variants = Vec::new();
+        loop {
+            if self.peek_next().token.kind == TokenKind::CloseBrace {
+                self.next(); // }
+                break;
+            }
+
+            let variant = self.expect_ident()?;
+            let discriminant = if self.peek_next().token.kind == TokenKind::Assign {
+                self.next(); // =
+                let value = self
+                    .expression()?
+                    .get_constant_value()
+                    .map_err(|error| ParseError::InvalidDiscriminant { variant, error })?;
+                Some(value)
+            } else {
+                None
+            };
+            variants.push((variant, discriminant));
+
+            let next = self.expect_next()?;
+            match next.token.kind {
+                TokenKind::Comma => (),
+                TokenKind::CloseBrace => break,
+                _ => return Err(ParseError::UnexpectedToken(next)),
+            }
+        }
+
+        Ok(variants)
+    }
+
+    /// Parses the value of `variable` after the `=`, which has to be a constant expression.
+    fn initializer(&mut self, variable: Ident) -> Result<HugValue, ParseError> {
+        self.expression()?
+            .get_constant_value()
+            .map_err(|error| ParseError::InvalidInitializer { variable, error })
+    }
+
+    pub fn variable_definition(&mut self) -> Result<HugTreeEntry, ParseError> {
+        let name = self.expect_next()?;
+        let name = match name.token.kind.expect_ident() {
+            Some(name) => name,
+            None => return Err(ParseError::UnexpectedToken(name)),
+        };
+
+        let next = self.expect_next()?;
+        let value = match next.token.kind {
+            TokenKind::Assign => self.initializer(name)?,
+            TokenKind::Colon => {
+                let _type = self.type_name()?;
+                if let TypeKind::Other(id) = _type {
+                    if !self.resolve(id) {
+                        return Err(ParseError::UnknownType(id));
+                    }
+                }
+                self.expect_next_kind(TokenKind::Assign)?;
+                let value = if let TypeKind::Other(_) = _type {
+                    // User-defined types have no literals, they are built with a struct literal
+                    self.initializer(name)?
+                } else {
+                    // The first literal is read as the annotated type, so `0xFF` can be a UInt8
+                    let first = self.expect_next()?;
+                    let first = HugValue::parse_from_type(_type, first.node.text, first.span)?;
+                    self.continue_expression(Expression::Literal(first))?
+                        .get_constant_value()
+                        .map_err(|error| ParseError::InvalidInitializer {
+                            variable: name,
+                            error,
+                        })?
+                };
+                if value.kind() != _type {
+                    return Err(ParseError::MismatchedType {
+                        variable: name,
+                        expected: _type,
+                        found: value.kind(),
+                    });
+                }
+                value
+            }
+            _ => return Err(ParseError::UnexpectedToken(next)),
+        };
+
+        let scope = self.current_scope();
+        scope.declare(name);
+        scope.members.insert(name, value.clone());
+
+        Ok(HugTreeEntry::VariableDefinition {
+            variable: name,
+            value,
+            annotations: self.annotation_state.take_custom(),
+            visibility: self.annotation_state.take_visibility(),
+        })
+    }
+
+    /// Parses the next entry like [`HugTreeParser::next_entry`], along with the source it
+    /// was parsed from.
+    fn spanned_entry(&mut self) -> Result<Option<Spanned<HugTreeEntry>>, ParseError> {
+        let start = self.peek_next().span.start;
+        let entry = self.next_entry()?;
+        Ok(entry.map(|entry| Spanned::new(entry, start..self.last_span.end)))
+    }
+
+    pub fn next_entry(&mut self) -> Result<Option<HugTreeEntry>, ParseError> {
+        let pair = self.next();
+        if pair.is_null() {
+            return Ok(None);
+        }
+
+        match pair.token.kind {
+            TokenKind::Literal(_)
+            | TokenKind::OpenBracket
+            | TokenKind::Not
+            | TokenKind::BinaryNot
+            | TokenKind::Subtract => {
+                Ok(Some(HugTreeEntry::Expression(self.expression_from(pair)?)))
+            }
+            TokenKind::Keyword(kind) => self.keyword(kind),
+            TokenKind::Identifier(id) => Ok(Some(self.identifier(id)?)),
+            TokenKind::Annotation(kind) => self.annotation(kind),
+            // TokenKind::Dot => todo!(),
+            // TokenKind::Ellipsis => todo!(),
+            // TokenKind::OpenParenthesis => todo!(),
+            // TokenKind::CloseParenthesis => todo!(),
+            // TokenKind::OpenBrace => todo!(),
+            // TokenKind::CloseBrace => todo!(),
+            // TokenKind::CloseBracket => todo!(),
+            // TokenKind::Colon => todo!(),
+            // TokenKind::Assign => todo!(),
+            // TokenKind::Add => todo!(),
+            // TokenKind::Multiply => todo!(),
+            // TokenKind::Divide => todo!(),
+            // TokenKind::Modulus => todo!(),
+            // TokenKind::And => todo!(),
+            // TokenKind::Or => todo!(),
+            // TokenKind::IsEqualTo => todo!(),
+            // TokenKind::IsNotEqualTo => todo!(),
+            // TokenKind::LessThan => todo!(),
+            // TokenKind::GreaterThan => todo!(),
+            // TokenKind::LessThanOrEquals => todo!(),
+            // TokenKind::GreaterThanOrEquals => todo!(),
+            // TokenKind::BinaryAnd => todo!(),
+            // TokenKind::BinaryOr => todo!(),
+            // TokenKind::BinaryXOr => todo!(),
+            // TokenKind::BinaryAndAssign => todo!(),
+            // TokenKind::BinaryOrAssign => todo!(),
+            // TokenKind::BinaryNotAssign => todo!(),
+            // TokenKind::BinaryXOrAssign => todo!(),
+            // TokenKind::ShiftLeft => todo!(),
+            // TokenKind::ShiftRight => todo!(),
+            // TokenKind::ShiftLeftOverflow => todo!(),
+            // TokenKind::ShiftRightOverflow => todo!(),
+            // TokenKind::ShiftLeftAssign => todo!(),
+            // TokenKind::ShiftRightAssign => todo!(),
+            // TokenKind::ShiftLeftOverflowAssign => todo!(),
+            // TokenKind::ShiftRightOverflowAssign => todo!(),
+            TokenKind::Unknown => Err(ParseError::UnexpectedToken(pair)),
+            _ => self.next_entry(),
+            // _ => unreachable!(),
         }
     }
 
-    pub fn parse(mut self) -> HugTree {
+    pub fn parse(mut self) -> Result<HugTree, ParseError> {
         self.annotation_state.reset();
-        while self.pairs.as_slice().len() > 0 {
+        self.declare_members();
+        while !self.pairs.as_slice().is_empty() {
             self.annotation_state.reset();
-            if let Some(entry) = self.next_entry() {
-                self.tree.entries.push(entry);
-            } else {
-                break;
+            if let Some(entry) = self.spanned_entry()? {
+                self.current_scope().entries.push(entry);
             }
         }
 
-        self.tree
+        let diagnostics = self
+            .errors
+            .into_iter()
+            .chain(self.references.iter().filter_map(|reference| {
+                let message = self.deprecated.get(reference)?;
+                Some(Diagnostic::warning(message).with_span(reference.span()))
+            }))
+            .collect();
+
+        Ok(HugTree {
+            root: self.scopes.pop().unwrap(),
+            unresolved: self.unresolved,
+            functions: self.functions,
+            function_spans: self.function_spans,
+            skipped_bodies: self.skipped_bodies,
+            diagnostics,
+        })
     }
 }
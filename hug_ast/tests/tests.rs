@@ -0,0 +1,2345 @@
+use hug_ast::{
+    diagnostic::Diagnostic,
+    expression::{BinaryOperator, CallArg, EvalOptions, Expression, UnaryOperator},
+    link,
+    parser::{HugTreeAnnotationState, HugTreeParser, ParseError, ParserOptions},
+    HugScope, HugTree, HugTreeEntry, LinkError, RenameError, Visibility,
+};
+use std::collections::HashMap;
+
+use hug_lexer::{
+    lex,
+    parser::generate_pairs,
+    tokenizer::{Base, LiteralKind, TokenKind, Tokenizer},
+};
+use hug_lib::{
+    error::{CallError, EvalError, ParseValueError, TypeError},
+    span::Spanned,
+    value::{HugValue, TypeKind},
+    Ident,
+};
+
+fn parse(program: &str) -> HugTree {
+    HugTree::from_token_pairs(lex(program)).unwrap()
+}
+
+const FORWARD_REFERENCE_PROGRAM: &str = r###"
+function a() {
+    b()
+}
+
+function b() {
+    missing()
+}
+"###;
+
+#[test]
+fn forward_reference() {
+    let tree = parse(FORWARD_REFERENCE_PROGRAM);
+    let (a, b, missing) = (Ident(0), Ident(1), Ident(2));
+
+    assert_eq!(tree.root().idents, vec![a, b]);
+    assert!(matches!(
+        tree.root().members.get(&b),
+        Some(HugValue::Function(_))
+    ));
+    assert_eq!(tree.unresolved, vec![missing]);
+
+    match &tree.root().entries[0].node {
+        HugTreeEntry::FunctionDefinition { function, body, .. } => {
+            assert_eq!(*function, a);
+            assert_eq!(body.entries.len(), 1);
+            assert!(matches!(
+                body.entries[0].node,
+                HugTreeEntry::FunctionCall { function, .. } if function == b
+            ));
+        }
+        other => panic!("Expected a function definition, got {:?}", other),
+    }
+}
+
+#[test]
+fn unexpected_eof() {
+    for program in ["let x =", "let x", "function a() {", "print(5"] {
+        assert_eq!(
+            HugTree::from_token_pairs(lex(program)).unwrap_err(),
+            ParseError::UnexpectedEof
+        );
+    }
+}
+
+#[test]
+fn peek_next() {
+    let mut parser = HugTreeParser::new(lex("let x = 1"));
+    assert_eq!(parser.peek_next().text, "let");
+    assert_eq!(parser.peek_next().text, "let");
+    assert_eq!(parser.next().text, "let");
+    assert_eq!(parser.peek_next().text, "x");
+
+    assert_eq!(parser.peek_nth(0).text, "x");
+    assert_eq!(parser.peek_nth(1).text, "=");
+    assert_eq!(parser.peek_nth(2).text, "1");
+    assert!(parser.peek_nth(3).is_null());
+    assert_eq!(parser.next().text, "x");
+
+    let mut parser = HugTreeParser::new(Vec::new());
+    assert!(parser.peek_next().is_null());
+    assert!(parser.peek_nth(0).is_null());
+}
+
+#[test]
+fn large_programs() {
+    // Parsing used to take quadratic time, as every lookahead copied all remaining tokens
+    let program = "1 + 2 * 3\n".repeat(20_000);
+    let tree = parse(&program);
+    assert_eq!(tree.root().entries.len(), 20_000);
+}
+
+#[test]
+fn expression_only() {
+    let expression = HugTreeParser::parse_expression_only(lex("add(1, (x))")).unwrap();
+    match expression {
+        Expression::Call { function, args } => {
+            assert_eq!(function, Ident(0));
+            assert!(matches!(
+                args[0],
+                CallArg::Positional(Expression::Literal(HugValue::Int32(1)))
+            ));
+            assert!(matches!(
+                args[1],
+                CallArg::Positional(Expression::Variable(Ident(1)))
+            ));
+        }
+        other => panic!("Expected a call, got {:?}", other),
+    }
+
+    assert!(matches!(
+        HugTreeParser::parse_expression_only(lex("5 6")),
+        Err(ParseError::UnexpectedToken(_))
+    ));
+}
+
+#[test]
+fn function_arity() {
+    let tree = parse("function f(a, b) {}");
+    let target = match tree.root().members.get(&Ident(0)) {
+        Some(HugValue::Function(target)) => *target,
+        other => panic!("Expected a function, got {:?}", other),
+    };
+
+    assert_eq!(tree.function_arity(target), Some(2));
+    assert_eq!(tree.check_call(target, 2), Ok(()));
+    assert_eq!(
+        tree.check_call(target, 1),
+        Err(CallError::ArityMismatch {
+            expected: 2,
+            found: 1
+        })
+    );
+}
+
+#[test]
+fn default_arguments() {
+    let tree = parse("function f(a, b = 2 * 3, c = \"x\" + \"y\") {}");
+    let function = &tree.functions[0];
+    assert_eq!(function.arity, 3);
+    let defaults: Vec<_> = function
+        .args
+        .iter()
+        .map(|arg| arg.default.clone())
+        .collect();
+    assert!(matches!(
+        defaults.as_slice(),
+        [None, Some(HugValue::Int32(6)), Some(HugValue::String(c))] if c == "xy"
+    ));
+
+    assert_eq!(tree.check_call(0, 1), Ok(()));
+    assert_eq!(tree.check_call(0, 3), Ok(()));
+    assert_eq!(
+        tree.check_call(0, 0),
+        Err(CallError::ArityMismatch {
+            expected: 1,
+            found: 0
+        })
+    );
+    assert_eq!(
+        tree.check_call(0, 4),
+        Err(CallError::ArityMismatch {
+            expected: 3,
+            found: 4
+        })
+    );
+
+    let error = |program| HugTree::from_token_pairs(lex(program)).unwrap_err();
+    assert_eq!(
+        error("let y = 1 function f(x = y + 1) {}"),
+        ParseError::InvalidDefault {
+            argument: Ident(2),
+            error: EvalError::NonConstant
+        }
+    );
+    assert_eq!(
+        error("function f(x = 1 / 0) {}"),
+        ParseError::InvalidDefault {
+            argument: Ident(1),
+            error: EvalError::DivideByZero
+        }
+    );
+    assert_eq!(
+        error("function f(x = 1, y) {}"),
+        ParseError::MissingDefault(Ident(2))
+    );
+    assert_eq!(
+        error("function f(a, b = 1, c = 2, d) {}"),
+        ParseError::MissingDefault(Ident(4))
+    );
+}
+
+#[test]
+fn keyword_only_arguments() {
+    let tree = parse("function f(a, *, b) {} function g(a, b = 1, *, c = 2, d = 3) {}");
+    let keyword_only = |target: usize| {
+        tree.functions[target]
+            .args
+            .iter()
+            .map(|arg| arg.keyword_only)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(tree.functions[0].arity, 2);
+    assert_eq!(keyword_only(0), [false, true]);
+    assert_eq!(keyword_only(1), [false, false, true, true]);
+
+    let (a, b) = (Ident(1), Ident(2));
+    assert_eq!(tree.check_named_call(0, 1, &[b]), Ok(()));
+    assert_eq!(tree.check_named_call(0, 0, &[b, a]), Ok(()));
+    assert_eq!(tree.check_call(0, 1), Err(CallError::MissingArgument(b)));
+    assert_eq!(
+        tree.check_named_call(0, 1, &[a, b]),
+        Err(CallError::DuplicateArgument(a))
+    );
+    assert_eq!(
+        tree.check_named_call(0, 1, &[b, b]),
+        Err(CallError::DuplicateArgument(b))
+    );
+    assert_eq!(
+        tree.check_named_call(0, 1, &[Ident(3)]),
+        Err(CallError::UnknownArgument(Ident(3)))
+    );
+    assert_eq!(
+        tree.check_named_call(0, 2, &[b]),
+        Err(CallError::ArityMismatch {
+            expected: 1,
+            found: 2
+        })
+    );
+    assert_eq!(tree.check_call(1, 1), Ok(()));
+    assert_eq!(tree.check_call(1, 2), Ok(()));
+    assert_eq!(
+        tree.check_call(1, 3),
+        Err(CallError::ArityMismatch {
+            expected: 2,
+            found: 3
+        })
+    );
+    assert!(!parse("function h(a) {}").functions[0].args[0].keyword_only);
+
+    // Keyword-only arguments don't need a default, even after one that has one
+    let tree = parse("function f(a = 1, *, b) {}");
+    assert!(tree.functions[0].args[1].default.is_none());
+    assert_eq!(tree.check_named_call(0, 0, &[b]), Ok(()));
+    assert_eq!(tree.check_call(0, 1), Err(CallError::MissingArgument(b)));
+
+    let tree = parse("function f(a, *, b) {} f(1, b = 2)");
+    match &tree.root().entries[1].node {
+        HugTreeEntry::FunctionCall { args, .. } => {
+            assert!(matches!(args[0], CallArg::Positional(_)));
+            assert!(matches!(
+                &args[1],
+                CallArg::Named(name, Expression::Literal(HugValue::Int32(2))) if *name == b
+            ));
+        }
+        other => panic!("Expected a function call, got {:?}", other),
+    }
+
+    let error = |program| HugTree::from_token_pairs(lex(program)).unwrap_err();
+    assert!(matches!(
+        error("function f(a, *) {}"),
+        ParseError::UnexpectedToken(pair) if pair.text == "*"
+    ));
+    assert!(matches!(
+        error("function f(a, *, b, *, c) {}"),
+        ParseError::UnexpectedToken(pair) if pair.text == "*"
+    ));
+    assert!(matches!(
+        error("function f(a, *, b) {} f(b = 1, 2)"),
+        ParseError::UnexpectedToken(pair) if pair.text == "2"
+    ));
+}
+
+#[test]
+fn logical_operators() {
+    let evaluate = |program| {
+        HugTreeParser::parse_expression_only(lex(program))
+            .unwrap()
+            .evaluate(|_| None)
+    };
+
+    assert_eq!(evaluate("false && y"), Ok(HugValue::Bool(false)));
+    assert_eq!(evaluate("true || y"), Ok(HugValue::Bool(true)));
+    assert_eq!(evaluate("true && !false"), Ok(HugValue::Bool(true)));
+    assert_eq!(evaluate("false && false || true"), Ok(HugValue::Bool(true)));
+    assert!(matches!(
+        evaluate("true && y"),
+        Err(EvalError::UndefinedVariable(_))
+    ));
+    assert_eq!(
+        evaluate("1 && true"),
+        Err(EvalError::TypeError(TypeError::new(
+            "Bool",
+            &HugValue::from(1)
+        )))
+    );
+    assert_eq!(
+        evaluate("false || 1"),
+        Err(EvalError::TypeError(TypeError::new(
+            "Bool",
+            &HugValue::from(1)
+        )))
+    );
+}
+
+#[test]
+fn in_operator() {
+    let map = HugValue::Map(HashMap::from([("a".to_string(), HugValue::from(1))]));
+    let evaluate = |program| {
+        HugTreeParser::parse_expression_only(lex(program))
+            .unwrap()
+            .evaluate(|_| Some(map.clone()))
+    };
+
+    assert_eq!(evaluate("2 in [1, 2, 3]"), Ok(HugValue::Bool(true)));
+    assert_eq!(evaluate("4 in [1, 2, 3]"), Ok(HugValue::Bool(false)));
+    assert_eq!(evaluate("\"a\" in m"), Ok(HugValue::Bool(true)));
+    assert_eq!(evaluate("\"b\" in m"), Ok(HugValue::Bool(false)));
+    assert_eq!(evaluate("2 in [1, 2] == true"), Ok(HugValue::Bool(true)));
+    assert_eq!(
+        evaluate("1 in m"),
+        Err(EvalError::TypeError(TypeError::new(
+            "String",
+            &HugValue::from(1)
+        )))
+    );
+    assert_eq!(
+        evaluate("1 in 2"),
+        Err(EvalError::TypeError(TypeError::new(
+            "Array or Map",
+            &HugValue::from(2)
+        )))
+    );
+}
+
+#[test]
+fn strict_options() {
+    let parse_with =
+        |program: &str, options| HugTreeParser::with_options(lex(program), options).parse();
+
+    assert!(parse_with("print(\"hi\")", ParserOptions::default()).is_ok());
+    assert_eq!(
+        parse_with("print(\"hi\")", ParserOptions::strict()).unwrap_err(),
+        ParseError::UndefinedName(Ident(0))
+    );
+    assert_eq!(
+        parse_with("@custom function f() {}", ParserOptions::strict()).unwrap_err(),
+        ParseError::UnknownAnnotation(Ident(0))
+    );
+
+    let fine_tuned = ParserOptions {
+        deny_undefined_names: Some(false),
+        ..ParserOptions::strict()
+    };
+    assert!(parse_with("print(\"hi\")", fine_tuned).is_ok());
+}
+
+#[test]
+fn clear_scope() {
+    let mut scope = parse("let a = 1 let b = 2 function c() {}").root().clone();
+    let capacities = (
+        scope.idents.capacity(),
+        scope.members.capacity(),
+        scope.entries.capacity(),
+    );
+
+    scope.clear();
+    assert!(scope.idents.is_empty() && scope.members.is_empty() && scope.entries.is_empty());
+    assert_eq!(
+        capacities,
+        (
+            scope.idents.capacity(),
+            scope.members.capacity(),
+            scope.entries.capacity()
+        )
+    );
+}
+
+#[test]
+fn evaluate_expression() {
+    let variables = |id| (id == Ident(0)).then(|| HugValue::from(5));
+
+    let defined = HugTreeParser::parse_expression_only(lex("x")).unwrap();
+    assert!(matches!(
+        defined.evaluate(variables),
+        Ok(HugValue::Int32(5))
+    ));
+
+    let undefined = Expression::Variable(Ident(1));
+    assert_eq!(
+        undefined.evaluate(variables).unwrap_err(),
+        EvalError::UndefinedVariable(Ident(1))
+    );
+
+    let call = HugTreeParser::parse_expression_only(lex("f()")).unwrap();
+    assert_eq!(
+        call.evaluate(variables).unwrap_err(),
+        EvalError::NonConstant
+    );
+
+    let division = HugTreeParser::parse_expression_only(lex("x / 0")).unwrap();
+    assert_eq!(
+        division.evaluate(variables).unwrap_err(),
+        EvalError::DivideByZero
+    );
+
+    let arithmetic = HugTreeParser::parse_expression_only(lex("x * 2 + 7 % x - 1")).unwrap();
+    assert!(matches!(
+        arithmetic.evaluate(variables),
+        Ok(HugValue::Int32(11))
+    ));
+    let mismatched = HugTreeParser::parse_expression_only(lex("x + 1.5")).unwrap();
+    assert_eq!(
+        mismatched.evaluate(variables).unwrap_err(),
+        EvalError::TypeError(TypeError {
+            expected: "Int32",
+            found: TypeKind::Float32
+        })
+    );
+}
+
+const DEPRECATED_PROGRAM: &str = r###"
+function main() {
+    foo()
+    bar()
+}
+
+@deprecated("use bar instead")
+function foo() {}
+
+function bar() {}
+
+@deprecated
+function baz() {}
+"###;
+
+#[test]
+fn deprecated_function() {
+    let tree = parse(DEPRECATED_PROGRAM);
+    assert_eq!(
+        tree.diagnostics,
+        vec![Diagnostic::warning("use bar instead").with_span(23..26)]
+    );
+    assert_eq!(
+        tree.diagnostics[0].render(DEPRECATED_PROGRAM),
+        "warning: use bar instead (at 3:5)"
+    );
+    assert!(tree.unresolved.is_empty());
+
+    let tree = parse("@deprecated function baz() {} baz()");
+    assert_eq!(
+        tree.diagnostics,
+        vec![Diagnostic::warning("this function is deprecated").with_span(30..33)]
+    );
+}
+
+#[test]
+fn if_without_else() {
+    let parse_with =
+        |program: &str, options| HugTreeParser::with_options(lex(program), options).parse();
+
+    let valued = "let c = 1 let x = 2 if c { x }";
+    let tree = parse(valued);
+    match &tree.root().entries[2].node {
+        HugTreeEntry::Expression(Expression::If {
+            then_branch,
+            else_branch: None,
+            ..
+        }) => assert!(matches!(
+            then_branch.value(),
+            Some(Expression::Variable(Ident(1)))
+        )),
+        other => panic!("Expected an if, got {:?}", other),
+    }
+    assert_eq!(
+        parse_with(valued, ParserOptions::strict()).unwrap_err(),
+        ParseError::ValuedIfWithoutElse
+    );
+
+    for program in [
+        "function print(s) {} let c = 1 if c { print(c) }",
+        "let c = 1 let x = 2 if c { x } else { c }",
+        "let c = 1 if c { if c {} }",
+    ] {
+        assert!(parse_with(program, ParserOptions::strict()).is_ok());
+    }
+    assert_eq!(
+        parse_with(
+            "let c = 1 if c { if c { 5 } else { 6 } }",
+            ParserOptions::strict()
+        )
+        .unwrap_err(),
+        ParseError::ValuedIfWithoutElse
+    );
+}
+
+#[test]
+fn predefined_names() {
+    let mut parser = HugTreeParser::with_options(lex("print(\"hi\")"), ParserOptions::strict());
+    parser.predefine("print", HugValue::ExternalFunction(|_| None));
+    parser.predefine("unused", HugValue::from(1));
+
+    let tree = parser.parse().unwrap();
+    assert_eq!(tree.root().idents, vec![Ident(0)]);
+    assert!(matches!(
+        tree.root().members.get(&Ident(0)),
+        Some(HugValue::ExternalFunction(_))
+    ));
+}
+
+#[test]
+fn spread_arguments() {
+    let args = |program| match HugTreeParser::parse_expression_only(lex(program)).unwrap() {
+        Expression::Call { args, .. } => args,
+        other => panic!("Expected a call, got {:?}", other),
+    };
+
+    let spread = args("f(...rest)");
+    assert_eq!(spread.len(), 1);
+    assert!(matches!(
+        spread[0],
+        CallArg::Spread(Expression::Variable(Ident(1)))
+    ));
+
+    let mixed = args("f(1, ...rest, x)");
+    assert!(matches!(
+        mixed[0],
+        CallArg::Positional(Expression::Literal(_))
+    ));
+    assert!(matches!(
+        mixed[1],
+        CallArg::Spread(Expression::Variable(Ident(1)))
+    ));
+    assert!(matches!(
+        mixed[2],
+        CallArg::Positional(Expression::Variable(Ident(2)))
+    ));
+}
+
+#[test]
+fn shadowing() {
+    let mut scope = parse("let x = 1").root().clone();
+    let x = Ident(0);
+
+    scope.push_shadow(x, HugValue::from(2));
+    scope.push_shadow(x, HugValue::from(3));
+    assert!(matches!(scope.resolve(x), Some(HugValue::Int32(3))));
+
+    assert!(matches!(scope.pop_shadow(x), Some(HugValue::Int32(3))));
+    assert!(matches!(scope.resolve(x), Some(HugValue::Int32(2))));
+    assert!(matches!(scope.pop_shadow(x), Some(HugValue::Int32(2))));
+    assert!(matches!(scope.resolve(x), Some(HugValue::Int32(1))));
+    assert!(scope.pop_shadow(x).is_none());
+
+    let y = Ident(1);
+    scope.push_shadow(y, HugValue::from(4));
+    assert!(scope.pop_shadow(y).is_some());
+    assert!(scope.resolve(y).is_none());
+}
+
+#[test]
+fn variable_initializers() {
+    let tree = parse("let x = 1 + 2 let y = -5 let z: UInt8 = 0xFF");
+    assert_eq!(tree.root().entries.len(), 3);
+    let value = |id| tree.root().members.get(&Ident(id)).unwrap();
+
+    assert!(matches!(value(0), HugValue::Int32(3)));
+    assert!(matches!(value(1), HugValue::Int32(-5)));
+    assert!(matches!(value(2), HugValue::UInt8(255)));
+    assert!(matches!(
+        &tree.root().entries[1].node,
+        HugTreeEntry::VariableDefinition {
+            value: HugValue::Int32(-5),
+            ..
+        }
+    ));
+
+    // Only the first literal takes the annotated type
+    assert!(matches!(
+        HugTree::from_token_pairs(lex("let z: Int64 = 2 * 3")),
+        Err(ParseError::InvalidInitializer {
+            error: EvalError::TypeError(_),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn typed_variables() {
+    let tree = parse("let a: UInt8 = 0xFF let b: Float64 = 1.5 let c: String = \"hi\"");
+    let value = |id| tree.root().members.get(&Ident(id)).unwrap();
+
+    assert!(matches!(value(0), HugValue::UInt8(255)));
+    assert!(matches!(value(2), HugValue::Float64(v) if *v == 1.5));
+    assert!(matches!(value(4), HugValue::String(v) if v == "hi"));
+
+    let tree = parse("let d: Float64 = 0x1.8p1 let e: Float32 = 0x1p-2");
+    assert!(matches!(tree.root().members[&Ident(0)], HugValue::Float64(v) if v == 3.0));
+    assert!(matches!(tree.root().members[&Ident(2)], HugValue::Float32(v) if v == 0.25));
+
+    assert_eq!(
+        HugTree::from_token_pairs(lex("let a: UInt8 = 256")).unwrap_err(),
+        ParseError::InvalidValue(ParseValueError::IntegerOverflow {
+            _type: TypeKind::UInt8,
+            value: "256".to_string(),
+            span: 15..18,
+        })
+    );
+    assert!(matches!(
+        HugTree::from_token_pairs(lex("let a: Int32 = \"a\"")),
+        Err(ParseError::InvalidValue(ParseValueError::Invalid { .. }))
+    ));
+    assert_eq!(
+        HugTree::from_token_pairs(lex("let a: Int8 = 0b1_0000_0000")).unwrap_err(),
+        ParseError::InvalidValue(ParseValueError::IntegerOverflow {
+            _type: TypeKind::Int8,
+            value: "0b1_0000_0000".to_string(),
+            span: 14..27,
+        })
+    );
+    for program in ["let 5 = 1", "let a 1", "let a = function"] {
+        assert!(matches!(
+            HugTree::from_token_pairs(lex(program)),
+            Err(ParseError::UnexpectedToken(_))
+        ));
+    }
+    assert_eq!(
+        HugTree::from_token_pairs(lex("let a = b")).unwrap_err(),
+        ParseError::InvalidInitializer {
+            variable: Ident(0),
+            error: EvalError::NonConstant
+        }
+    );
+    assert_eq!(
+        HugTreeParser::with_options(lex("let p: Point = 1"), ParserOptions::strict())
+            .parse()
+            .unwrap_err(),
+        ParseError::UndefinedName(Ident(1))
+    );
+    assert_eq!(
+        HugTree::from_token_pairs(lex("let p: Point = 1")).unwrap_err(),
+        ParseError::UnknownType(Ident(1))
+    );
+
+    let point = "type Point { x: Int32, y: Int32 } let p: Point = ";
+    let (point_type, x, y, p) = (Ident(0), Ident(1), Ident(3), Ident(4));
+    let tree = parse(&format!("{}Point {{ x: 1, y: 2 }}", point));
+    assert_eq!(
+        tree.root().members.get(&p),
+        Some(&HugValue::Struct {
+            type_name: point_type,
+            fields: HashMap::from([(x, HugValue::from(1)), (y, HugValue::from(2))]),
+        })
+    );
+    assert_eq!(
+        HugTree::from_token_pairs(lex(&format!("{}1", point))).unwrap_err(),
+        ParseError::MismatchedType {
+            variable: p,
+            expected: TypeKind::Other(point_type),
+            found: TypeKind::Int32,
+        }
+    );
+    assert_eq!(
+        HugTree::from_token_pairs(lex("type A {} type B {} let a: A = B {}")).unwrap_err(),
+        ParseError::MismatchedType {
+            variable: Ident(2),
+            expected: TypeKind::Other(Ident(0)),
+            found: TypeKind::Other(Ident(1)),
+        }
+    );
+    assert_eq!(
+        HugTree::from_token_pairs(lex(&format!("{}Point {{ x: q, y: 2 }}", point))).unwrap_err(),
+        ParseError::InvalidInitializer {
+            variable: p,
+            error: EvalError::NonConstant,
+        }
+    );
+}
+
+#[test]
+fn assignment() {
+    let tree = parse("let x = 1 let y = 2 x = y");
+    assert!(matches!(
+        tree.root().entries[2].node,
+        HugTreeEntry::Assignment {
+            variable: Ident(0),
+            value: Expression::Variable(Ident(1))
+        }
+    ));
+
+    let tree = parse("let x = 1 function f() { x = 5 }");
+    match &tree.root().entries[1].node {
+        HugTreeEntry::FunctionDefinition { body, .. } => assert!(matches!(
+            body.entries[0].node,
+            HugTreeEntry::Assignment {
+                variable: Ident(0),
+                value: Expression::Literal(HugValue::Int32(5))
+            }
+        )),
+        other => panic!("Expected a function definition, got {:?}", other),
+    }
+
+    assert_eq!(
+        HugTree::from_token_pairs(lex("x = 5")).unwrap_err(),
+        ParseError::UndeclaredAssignment(Ident(0))
+    );
+}
+
+#[test]
+fn compound_assignment() {
+    let operators = [
+        ("+=", BinaryOperator::Add),
+        ("-=", BinaryOperator::Subtract),
+        ("*=", BinaryOperator::Multiply),
+        ("/=", BinaryOperator::Divide),
+        ("%=", BinaryOperator::Modulus),
+    ];
+
+    for (token, expected) in operators {
+        let tree = parse(&format!("let x = 1 x {} 2 + 3", token));
+        match &tree.root().entries[1].node {
+            HugTreeEntry::Assignment {
+                variable: Ident(0),
+                value: Expression::BinaryOp { op, lhs, rhs },
+            } => {
+                assert_eq!(*op, expected, "{}", token);
+                assert!(matches!(lhs.node, Expression::Variable(Ident(0))));
+                assert_eq!(grouping(rhs), "(2 Add 3)");
+            }
+            other => panic!("Expected an assignment for {}, got {:?}", token, other),
+        }
+    }
+
+    assert_eq!(
+        HugTree::from_token_pairs(lex("x += 5")).unwrap_err(),
+        ParseError::UndeclaredAssignment(Ident(0))
+    );
+}
+
+#[test]
+fn function_spans() {
+    let program = "let x = 1\n@deprecated function f(a) { x = a }\nfunction g() {}";
+    let mut tree = parse(program);
+
+    let target = |tree: &HugTree, id| match tree.root().members.get(&Ident(id)) {
+        Some(HugValue::Function(target)) => *target,
+        other => panic!("Expected a function, got {:?}", other),
+    };
+
+    let f = tree.function_span(target(&tree, 1)).unwrap();
+    assert_eq!(&program[f], "function f(a) { x = a }");
+    let g = tree.function_span(target(&tree, 3)).unwrap();
+    assert_eq!(&program[g.clone()], "function g() {}");
+
+    tree.merge_with(parse("function h() {}"));
+    assert_eq!(tree.function_span(2), Some(0..15));
+    assert_eq!(tree.function_span(1), Some(g));
+
+    let program = "function a() { function b() {} }";
+    let tree = parse(program);
+    assert_eq!(tree.function_span(0), Some(0..program.len()));
+    assert_eq!(&program[tree.function_span(1).unwrap()], "function b() {}");
+}
+
+#[test]
+fn entry_spans() {
+    let program =
+        "let x = 1 public function f() { x = 2 } @cfg { let y = 3 } if x: x = 4 else if x {}";
+    let tree = parse(program);
+    let text = |entry: &Spanned<HugTreeEntry>| &program[entry.span()];
+    let root = &tree.root().entries;
+    assert_eq!(root.len(), 4);
+    assert_eq!(text(&root[0]), "let x = 1");
+    assert_eq!(text(&root[1]), "public function f() { x = 2 }");
+    assert_eq!(text(&root[2]), "let y = 3");
+    assert_eq!(text(&root[3]), "if x: x = 4 else if x {}");
+
+    match &root[1].node {
+        HugTreeEntry::FunctionDefinition { body, .. } => {
+            assert_eq!(text(&body.entries[0]), "x = 2")
+        }
+        other => panic!("Expected a function, got {:?}", other),
+    }
+    match &root[3].node {
+        HugTreeEntry::Expression(Expression::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        }) => {
+            assert_eq!(text(&then_branch.entries[0]), "x = 4");
+            assert_eq!(text(&else_branch.entries[0]), "if x {}");
+        }
+        other => panic!("Expected an if with an else, got {:?}", other),
+    }
+
+    let mut scope = HugScope::new();
+    scope.extend([HugTreeEntry::Return(None)]);
+    assert_eq!(scope.entries[0].span(), 0..0);
+
+    let program = "(a + 1) * -b[c] + typeof d.e";
+    let text = |expression: &Spanned<Expression>| &program[expression.span()];
+    let Expression::BinaryOp { lhs, rhs, .. } =
+        HugTreeParser::parse_expression_only(lex(program)).unwrap()
+    else {
+        panic!("Expected a binary operator");
+    };
+    assert_eq!(text(&lhs), "(a + 1) * -b[c]");
+    assert_eq!(text(&rhs), "typeof d.e");
+    match (&lhs.node, &rhs.node) {
+        (Expression::BinaryOp { lhs, rhs, .. }, Expression::TypeOf(value)) => {
+            assert_eq!(text(lhs), "(a + 1)");
+            assert_eq!(text(rhs), "-b[c]");
+            assert_eq!(text(value), "d.e");
+            let Expression::UnaryOp { operand, .. } = &rhs.node else {
+                panic!("Expected a unary operator, got {:?}", rhs);
+            };
+            let Expression::Index { base, index } = &operand.node else {
+                panic!("Expected an index expression, got {:?}", operand);
+            };
+            assert_eq!((text(base), text(index)), ("b", "c"));
+        }
+        other => panic!("Expected a product and a typeof, got {:?}", other),
+    }
+}
+
+#[test]
+fn field_access() {
+    let (a, b, c, d) = (Ident(0), Ident(1), Ident(2), Ident(3));
+
+    match HugTreeParser::parse_expression_only(lex("a.b.c")).unwrap() {
+        Expression::FieldAccess { base, field } => {
+            assert_eq!(field, c);
+            assert!(matches!(
+                base.node,
+                Expression::FieldAccess { base, field } if field == b && matches!(base.node, Expression::Variable(id) if id == a)
+            ));
+        }
+        other => panic!("Expected a field access, got {:?}", other),
+    }
+
+    match HugTreeParser::parse_expression_only(lex("a.b.c(d)")).unwrap() {
+        Expression::MethodCall { base, method, args } => {
+            assert_eq!(method, c);
+            assert!(matches!(base.node, Expression::FieldAccess { field, .. } if field == b));
+            assert!(matches!(args[0], CallArg::Positional(Expression::Variable(id)) if id == d));
+        }
+        other => panic!("Expected a method call, got {:?}", other),
+    }
+
+    assert!(matches!(
+        HugTreeParser::parse_expression_only(lex("a.5")),
+        Err(ParseError::UnexpectedToken(_))
+    ));
+    assert_eq!(
+        HugTreeParser::parse_expression_only(lex("a.")).unwrap_err(),
+        ParseError::UnexpectedEof
+    );
+
+    let tree = parse("let a = 1 a.b()");
+    assert!(matches!(
+        tree.root().entries[1].node,
+        HugTreeEntry::Expression(Expression::MethodCall { .. })
+    ));
+}
+
+/// Writes `expression` with every binary operation in parentheses, to check how it was grouped.
+fn grouping(expression: &Expression) -> String {
+    match expression {
+        Expression::Literal(HugValue::Int32(v)) => v.to_string(),
+        Expression::Variable(Ident(id)) => format!("v{}", id),
+        Expression::BinaryOp { op, lhs, rhs } => {
+            format!("({} {:?} {})", grouping(lhs), op, grouping(rhs))
+        }
+        other => panic!("Unexpected expression {:?}", other),
+    }
+}
+
+#[test]
+fn operator_precedence() {
+    let parse_grouping =
+        |program| grouping(&HugTreeParser::parse_expression_only(lex(program)).unwrap());
+
+    match HugTreeParser::parse_expression_only(lex("2 + 3 * 4")).unwrap() {
+        Expression::BinaryOp { op, lhs, rhs } => {
+            assert_eq!(op, BinaryOperator::Add);
+            assert!(matches!(lhs.node, Expression::Literal(HugValue::Int32(2))));
+            assert!(matches!(
+                rhs.node,
+                Expression::BinaryOp {
+                    op: BinaryOperator::Multiply,
+                    ..
+                }
+            ));
+        }
+        other => panic!("Expected a binary operation, got {:?}", other),
+    }
+
+    assert_eq!(parse_grouping("1 + 2 * 3"), "(1 Add (2 Multiply 3))");
+    assert_eq!(parse_grouping("1 - 2 - 3"), "((1 Subtract 2) Subtract 3)");
+    assert_eq!(parse_grouping("(1 + 2) * 3"), "((1 Add 2) Multiply 3)");
+    assert_eq!(
+        parse_grouping("1 * 2 + 3 % 4 / 5"),
+        "((1 Multiply 2) Add ((3 Modulus 4) Divide 5))"
+    );
+    assert_eq!(
+        parse_grouping("a < b + 1 && c || d"),
+        "(((v0 LessThan (v1 Add 1)) And v2) Or v3)"
+    );
+    assert_eq!(
+        parse_grouping("a || b && c == d"),
+        "(v0 Or (v1 And (v2 IsEqualTo v3)))"
+    );
+    assert_eq!(
+        parse_grouping("a + 1 in b && c"),
+        "(((v0 Add 1) In v1) And v2)"
+    );
+    assert_eq!(parse_grouping("a in b == c"), "((v0 In v1) IsEqualTo v2)");
+
+    assert!(matches!(
+        HugTreeParser::parse_expression_only(lex("1 + 2 3")),
+        Err(ParseError::UnexpectedToken(_))
+    ));
+    assert_eq!(
+        HugTreeParser::parse_expression_only(lex("1 +")).unwrap_err(),
+        ParseError::UnexpectedEof
+    );
+
+    let tree = parse("let a = 1 a * 2 + 1");
+    assert!(matches!(
+        &tree.root().entries[1].node,
+        HugTreeEntry::Expression(e) if grouping(e) == "((v0 Multiply 2) Add 1)"
+    ));
+}
+
+#[test]
+fn root_scope() {
+    let tree = parse("let a = 1 let b = \"two\"");
+    let root = tree.root();
+
+    assert_eq!(root.idents, vec![Ident(0), Ident(1)]);
+    assert!(matches!(root.resolve(Ident(0)), Some(HugValue::Int32(1))));
+    assert!(matches!(root.resolve(Ident(1)), Some(HugValue::String(v)) if v == "two"));
+}
+
+#[test]
+fn boolean_literals() {
+    assert!(matches!(
+        HugTreeParser::parse_expression_only(lex("true")).unwrap(),
+        Expression::Literal(HugValue::Bool(true))
+    ));
+
+    let tree = parse("let a = false let b: Bool = true");
+    assert!(matches!(
+        tree.root().resolve(Ident(0)),
+        Some(HugValue::Bool(false))
+    ));
+    assert!(matches!(
+        tree.root().resolve(Ident(1)),
+        Some(HugValue::Bool(true))
+    ));
+}
+
+#[test]
+fn optional_chaining() {
+    let (a, b, c) = (Ident(0), Ident(1), Ident(2));
+
+    match HugTreeParser::parse_expression_only(lex("a?.b.c")).unwrap() {
+        Expression::FieldAccess { base, field } => {
+            assert_eq!(field, c);
+            assert!(matches!(
+                base.node,
+                Expression::OptionalFieldAccess { base, field } if field == b && matches!(base.node, Expression::Variable(id) if id == a)
+            ));
+        }
+        other => panic!("Expected a field access, got {:?}", other),
+    }
+
+    let optional = HugTreeParser::parse_expression_only(lex("a?.b")).unwrap();
+    assert!(matches!(
+        optional.evaluate(|_| Some(HugValue::Unit)),
+        Ok(HugValue::Unit)
+    ));
+    assert_eq!(
+        optional.evaluate(|_| Some(HugValue::from(1))).unwrap_err(),
+        EvalError::NonConstant
+    );
+}
+
+#[test]
+fn comparisons() {
+    let evaluate = |program| {
+        HugTreeParser::parse_expression_only(lex(program))
+            .unwrap()
+            .evaluate(|_| None)
+    };
+
+    assert!(matches!(evaluate("3 < 5"), Ok(HugValue::Bool(true))));
+    assert!(matches!(evaluate("3 >= 5"), Ok(HugValue::Bool(false))));
+    assert!(matches!(evaluate("2 + 3 <= 5"), Ok(HugValue::Bool(true))));
+    assert!(matches!(
+        evaluate("\"a\" == \"a\""),
+        Ok(HugValue::Bool(true))
+    ));
+    assert!(matches!(
+        evaluate("\"a\" != \"a\""),
+        Ok(HugValue::Bool(false))
+    ));
+    assert!(matches!(
+        evaluate("\"a\" < \"b\""),
+        Ok(HugValue::Bool(true))
+    ));
+    assert!(matches!(
+        evaluate("true == false"),
+        Ok(HugValue::Bool(false))
+    ));
+
+    assert_eq!(
+        evaluate("1 < \"a\"").unwrap_err(),
+        EvalError::TypeError(TypeError {
+            expected: "Int32",
+            found: TypeKind::String
+        })
+    );
+    assert_eq!(
+        evaluate("1 == 1.0").unwrap_err(),
+        EvalError::TypeError(TypeError {
+            expected: "Int32",
+            found: TypeKind::Float32
+        })
+    );
+}
+
+const RECOVERY_PROGRAM: &str = r###"
+let a = 1
+function f() {
+    a = 2
+    c = { 3 }
+    let b: Int32 = "b"
+    let d = 4
+}
+"###;
+
+#[test]
+fn scope_recovery() {
+    let tree = parse(RECOVERY_PROGRAM);
+    match &tree.root().entries[1].node {
+        HugTreeEntry::FunctionDefinition { body, .. } => {
+            assert_eq!(body.entries.len(), 2);
+            assert!(matches!(
+                body.entries[0].node,
+                HugTreeEntry::Assignment {
+                    variable: Ident(0),
+                    ..
+                }
+            ));
+            assert!(matches!(
+                body.entries[1].node,
+                HugTreeEntry::VariableDefinition { .. }
+            ));
+        }
+        other => panic!("Expected a function definition, got {:?}", other),
+    }
+
+    assert_eq!(tree.diagnostics.len(), 2);
+    assert!(tree.diagnostics.iter().all(Diagnostic::is_error));
+    assert!(tree.diagnostics[0]
+        .render(RECOVERY_PROGRAM)
+        .ends_with("(at 5:7)"));
+    assert!(tree.diagnostics[1]
+        .render(RECOVERY_PROGRAM)
+        .ends_with("(at 6:20)"));
+
+    // A statement that runs into the closing brace of its block still closes the block
+    for body in ["let a = }", "a = }", "f(}", "let a = 1 a = }"] {
+        let program = format!("function f() {{ {} let b = 2", body);
+        let tree = parse(&program);
+        assert_eq!(tree.diagnostics.len(), 1, "{}", program);
+        assert!(tree.root().entries.iter().any(|entry| matches!(
+            &entry.node,
+            HugTreeEntry::VariableDefinition { value, .. } if *value == HugValue::from(2)
+        )));
+    }
+
+    // Closing braces of blocks inside the failed statement don't close the outer block
+    for body in [
+        "type T { a: }",
+        "type T { a: 5, b: Int32 }",
+        "f(function() { })",
+    ] {
+        let program = format!("function f() {{ {} let y = 1 }}\nlet z = 2", body);
+        let tree = parse(&program);
+        assert_eq!(tree.diagnostics.len(), 1, "{}", program);
+        match &tree.root().entries[..] {
+            [function, variable] => {
+                let HugTreeEntry::FunctionDefinition { body, .. } = &function.node else {
+                    panic!("Expected a function definition, got {:?}", function);
+                };
+                assert!(matches!(
+                    body.entries[..],
+                    [Spanned {
+                        node: HugTreeEntry::VariableDefinition { .. },
+                        ..
+                    }]
+                ));
+                assert!(matches!(
+                    variable.node,
+                    HugTreeEntry::VariableDefinition {
+                        value: HugValue::Int32(2),
+                        ..
+                    }
+                ));
+            }
+            other => panic!("Expected a function and a variable, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn errors_mid_parse() {
+    let mut parser = HugTreeParser::new(lex("let y = 2 x = 1 } let z = \"three\" }"));
+    assert!(parser.errors().is_empty());
+
+    assert_eq!(parser.scope().unwrap().entries.len(), 1);
+    assert_eq!(
+        parser.errors(),
+        [
+            Diagnostic::error(ParseError::UndeclaredAssignment(Ident(1)).to_string())
+                .with_span(12..13)
+        ]
+    );
+
+    assert_eq!(parser.scope().unwrap().entries.len(), 1);
+    assert_eq!(parser.errors().len(), 1);
+}
+
+#[test]
+fn else_if_chain() {
+    let tree = parse("let a = 1 let b = 2 if a { 1 } else if b { 2 } else { 3 }");
+    let else_branch = match &tree.root().entries[2].node {
+        HugTreeEntry::Expression(Expression::If {
+            else_branch: Some(branch),
+            ..
+        }) => branch,
+        other => panic!("Expected an if with an else, got {:?}", other),
+    };
+
+    assert_eq!(else_branch.entries.len(), 1);
+    match else_branch.value() {
+        Some(Expression::If {
+            condition,
+            else_branch: Some(last),
+            ..
+        }) => {
+            assert!(matches!(condition.node, Expression::Variable(Ident(1))));
+            assert!(matches!(
+                last.value(),
+                Some(Expression::Literal(HugValue::Int32(3)))
+            ));
+        }
+        other => panic!("Expected an else if, got {:?}", other),
+    }
+
+    let strict =
+        |program| HugTreeParser::with_options(lex(program), ParserOptions::strict()).parse();
+    assert!(strict("let a = 1 if a { 1 } else if a { 2 } else { 3 }").is_ok());
+    assert_eq!(
+        strict("let a = 1 if a { 1 } else if a { 2 }").unwrap_err(),
+        ParseError::ValuedIfWithoutElse
+    );
+}
+
+#[test]
+fn collect_scope() {
+    let scope: HugScope = vec![
+        HugTreeEntry::VariableDefinition {
+            variable: Ident(0),
+            value: HugValue::from(1),
+            annotations: HashMap::new(),
+            visibility: Visibility::Private,
+        },
+        HugTreeEntry::FunctionDefinition {
+            function: Ident(1),
+            args: Vec::new(),
+            arg_types: Vec::new(),
+            return_type: None,
+            body: HugScope::new(),
+            visibility: Visibility::Private,
+        },
+        HugTreeEntry::Assignment {
+            variable: Ident(0),
+            value: Expression::Literal(HugValue::from(2)),
+        },
+    ]
+    .into_iter()
+    .collect();
+
+    assert_eq!(scope.idents, vec![Ident(0), Ident(1)]);
+    assert_eq!(scope.entries.len(), 3);
+    assert!(matches!(scope.resolve(Ident(0)), Some(HugValue::Int32(1))));
+    assert!(scope.resolve(Ident(1)).is_none());
+}
+
+#[test]
+fn extend_scope() {
+    let mut scope = parse("let x = 1").root().clone();
+    scope.extend(vec![
+        HugTreeEntry::Use {
+            path: vec![Ident(1), Ident(2)],
+        },
+        HugTreeEntry::Expression(Expression::Variable(Ident(0))),
+    ]);
+
+    assert_eq!(scope.idents, vec![Ident(0), Ident(2)]);
+    assert_eq!(scope.entries.len(), 3);
+    assert!(matches!(
+        scope.entries[0].node,
+        HugTreeEntry::VariableDefinition { .. }
+    ));
+    assert!(matches!(scope.entries[1].node, HugTreeEntry::Use { .. }));
+    assert!(matches!(
+        scope.entries[2].node,
+        HugTreeEntry::Expression(Expression::Variable(Ident(0)))
+    ));
+}
+
+#[test]
+fn while_loop() {
+    let tree = parse("let x = 0 while x < 10 { x = x + 1 }");
+    match &tree.root().entries[1].node {
+        HugTreeEntry::While { condition, body } => {
+            assert_eq!(grouping(condition), "(v0 LessThan 10)");
+            match &body.entries[0].node {
+                HugTreeEntry::Assignment { variable, value } => {
+                    assert_eq!(*variable, Ident(0));
+                    assert_eq!(grouping(value), "(v0 Add 1)");
+                }
+                other => panic!("Expected an assignment, got {:?}", other),
+            }
+        }
+        other => panic!("Expected a while loop, got {:?}", other),
+    }
+
+    assert_eq!(
+        HugTree::from_token_pairs(lex("while x < 10")).unwrap_err(),
+        ParseError::UnexpectedEof
+    );
+}
+
+#[test]
+fn statement_bodies() {
+    let tree = parse("let x = 0 if x < 1: x = 1 else: let y = 2 while x < 10: x = x + 1");
+    match &tree.root().entries[1].node {
+        HugTreeEntry::Expression(Expression::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        }) => {
+            assert!(matches!(
+                then_branch.entries[..],
+                [Spanned { node: HugTreeEntry::Assignment { variable, .. }, .. }]
+                    if variable == Ident(0)
+            ));
+            assert!(matches!(
+                else_branch.entries[..],
+                [Spanned {
+                    node: HugTreeEntry::VariableDefinition { .. },
+                    ..
+                }]
+            ));
+            assert!(else_branch.is_declared(Ident(1)));
+        }
+        other => panic!("Expected an if with an else, got {:?}", other),
+    }
+    match &tree.root().entries[2].node {
+        HugTreeEntry::While { body, .. } => match &body.entries[..] {
+            [Spanned {
+                node: HugTreeEntry::Assignment { value, .. },
+                ..
+            }] => assert_eq!(grouping(value), "(v0 Add 1)"),
+            other => panic!("Expected a single assignment, got {:?}", other),
+        },
+        other => panic!("Expected a while loop, got {:?}", other),
+    }
+    // The `let` in the else branch is scoped to it
+    assert!(!tree.root().is_declared(Ident(1)));
+    assert_eq!(tree.root().entries.len(), 3);
+
+    let tree = parse("let x = 0 if x { x = 1 } while x < 10 { x = x + 1 }");
+    assert_eq!(tree.root().entries.len(), 3);
+
+    assert!(matches!(
+        HugTree::from_token_pairs(lex("let x = 0 if x x = 1")).unwrap_err(),
+        ParseError::ExpectedToken {
+            expected: TokenKind::OpenBrace,
+            found,
+        } if found.text == "x"
+    ));
+    assert_eq!(
+        HugTree::from_token_pairs(lex("let x = 0 while x:")).unwrap_err(),
+        ParseError::UnexpectedEof
+    );
+}
+
+#[test]
+fn local_count() {
+    let tree = parse("let a = 1 let b = 2 while a < b { let c = 3 a = c } let d = 4");
+    assert_eq!(tree.root().local_count(), 3);
+    match &tree.root().entries[2].node {
+        HugTreeEntry::While { body, .. } => assert_eq!(body.local_count(), 1),
+        other => panic!("Expected a while loop, got {:?}", other),
+    }
+    assert_eq!(HugScope::new().local_count(), 0);
+}
+
+#[test]
+fn return_types() {
+    let return_type = |program| match &parse(program).root().entries[0].node {
+        HugTreeEntry::FunctionDefinition { return_type, .. } => *return_type,
+        other => panic!("Expected a function definition, got {:?}", other),
+    };
+
+    assert_eq!(
+        return_type("function f() -> Int32 {}"),
+        Some(TypeKind::Int32)
+    );
+    assert_eq!(return_type("function g() {}"), None);
+    assert_eq!(
+        return_type("function h(a) -> Point {}"),
+        Some(TypeKind::Other(Ident(2)))
+    );
+
+    assert!(matches!(
+        HugTree::from_token_pairs(lex("function f() -> 5 {}")),
+        Err(ParseError::ExpectedType { found }) if found.text == "5"
+    ));
+}
+
+#[test]
+fn argument_types() {
+    let arg_types = |program| match &parse(program).root().entries[0].node {
+        HugTreeEntry::FunctionDefinition { arg_types, .. } => arg_types.clone(),
+        other => panic!("Expected a function definition, got {:?}", other),
+    };
+
+    assert_eq!(
+        arg_types("function f(a: Int32, b, c: String = \"c\") {}"),
+        [Some(TypeKind::Int32), None, Some(TypeKind::String)]
+    );
+    // Names that aren't built-in types are user-defined ones
+    assert_eq!(
+        arg_types("function f(x: notAType) {}"),
+        [Some(TypeKind::Other(Ident(2)))]
+    );
+
+    match HugTree::from_token_pairs(lex("function f(x: 5) {}")).unwrap_err() {
+        ParseError::ExpectedType { found } => {
+            assert_eq!(found.text, "5");
+            assert_eq!(found.span, 14..15);
+        }
+        other => panic!("Expected an ExpectedType error, got {:?}", other),
+    }
+}
+
+const SOURCE_ORDER_PROGRAM: &str = r###"
+let a = 1
+print(a)
+let b = 2
+a + b
+print(b)
+function print(value) {}
+a = 3
+"###;
+
+#[test]
+fn source_order() {
+    let kinds: Vec<&str> = parse(SOURCE_ORDER_PROGRAM)
+        .root()
+        .entries
+        .iter()
+        .map(|entry| match &entry.node {
+            HugTreeEntry::VariableDefinition { .. } => "let",
+            HugTreeEntry::FunctionCall { .. } => "call",
+            HugTreeEntry::Expression(_) => "expression",
+            HugTreeEntry::FunctionDefinition { .. } => "function",
+            HugTreeEntry::Assignment { .. } => "assignment",
+            other => panic!("Unexpected entry {:?}", other),
+        })
+        .collect();
+
+    assert_eq!(
+        kinds,
+        [
+            "let",
+            "call",
+            "let",
+            "expression",
+            "call",
+            "function",
+            "assignment"
+        ]
+    );
+}
+
+#[test]
+fn custom_annotations() {
+    let mut state = HugTreeAnnotationState::new();
+    let (a, b) = (Ident(0), Ident(1));
+
+    assert_eq!(state.push_custom(a, HashMap::new()), Ok(()));
+    let args = HashMap::from([("x".to_string(), (LiteralKind::String, "1".to_string()))]);
+    assert_eq!(state.push_custom(b, args.clone()), Ok(()));
+    assert_eq!(state.get_custom(b), Some(&args));
+
+    assert_eq!(
+        state.push_custom(b, HashMap::new()),
+        Err(ParseError::DuplicateAnnotation(b))
+    );
+    assert_eq!(state.get_custom(b), Some(&args));
+
+    assert!(HugTree::from_token_pairs(lex("@first @second function f() {}")).is_ok());
+
+    let tree = parse("@config(env = \"prod\", level = 2) let x = 1 let y = 2");
+    let annotations = |i: usize| match &tree.root().entries[i].node {
+        HugTreeEntry::VariableDefinition { annotations, .. } => annotations.clone(),
+        other => panic!("Expected a variable, got {:?}", other),
+    };
+    let config = &annotations(0)[&Ident(0)];
+    assert_eq!(config["env"], (LiteralKind::String, "prod".to_string()));
+    assert_eq!(
+        config["level"],
+        (LiteralKind::Integer(Base::Decimal), "2".to_string())
+    );
+    assert!(annotations(1).is_empty());
+    assert_eq!(
+        HugTree::from_token_pairs(lex("@first @first function f() {}")).unwrap_err(),
+        ParseError::DuplicateAnnotation(Ident(0))
+    );
+}
+
+#[test]
+fn annotated_blocks() {
+    let program = "@cfg(feature = \"x\") { let a = 1 let b = 2 } let c = 3";
+    let mut tokenizer = Tokenizer::new(program);
+    let tokens = tokenizer.tokenize();
+    let tree = HugTree::from_token_pairs(generate_pairs(program, tokens)).unwrap();
+    let cfg = tokenizer.idents["cfg"];
+    let annotations = |i: usize| match &tree.root().entries[i].node {
+        HugTreeEntry::VariableDefinition { annotations, .. } => annotations.clone(),
+        other => panic!("Expected a variable, got {:?}", other),
+    };
+
+    assert_eq!(tree.root().entries.len(), 3);
+    for i in 0..2 {
+        assert_eq!(
+            annotations(i)[&cfg]["feature"],
+            (LiteralKind::String, "x".to_string())
+        );
+    }
+    assert!(annotations(2).is_empty());
+    assert!(tree.root().is_declared(tokenizer.idents["b"]));
+
+    assert_eq!(parse("@cfg {} let c = 3").root().entries.len(), 1);
+    assert_eq!(
+        HugTree::from_token_pairs(lex("@cfg { let a = 1")).unwrap_err(),
+        ParseError::UnexpectedEof
+    );
+}
+
+#[test]
+fn annotation_arguments() {
+    let arguments = |program| {
+        HugTreeParser::new(lex(program))
+            .annotation_arguments()
+            .unwrap()
+    };
+
+    let args = arguments("(name = \"hug\", count = 5, enabled = true)");
+    assert_eq!(args["name"], (LiteralKind::String, "hug".to_string()));
+    assert_eq!(
+        args["count"],
+        (LiteralKind::Integer(Base::Decimal), "5".to_string())
+    );
+    assert_eq!(args["enabled"], (LiteralKind::Boolean, "true".to_string()));
+
+    assert!(arguments("function f() {}").is_empty());
+}
+
+#[test]
+fn module_definitions() {
+    let tree =
+        parse("module outer { let x = 1 function f() { return x } module inner { let y = 2 } }");
+    let (outer, x, f, inner, y) = (Ident(0), Ident(1), Ident(2), Ident(3), Ident(4));
+
+    assert_eq!(tree.root().idents, vec![outer]);
+    let HugTreeEntry::ModuleDefinition { module, body } = &tree.root().entries[0].node else {
+        panic!(
+            "Expected a module definition, got {:?}",
+            tree.root().entries[0].node
+        );
+    };
+    assert_eq!(*module, outer);
+    assert_eq!(body.idents, vec![f, inner, x]);
+    assert_eq!(body.entries.len(), 3);
+    assert!(matches!(
+        &body.entries[0].node,
+        HugTreeEntry::VariableDefinition { variable, .. } if *variable == x
+    ));
+    assert!(matches!(
+        &body.entries[1].node,
+        HugTreeEntry::FunctionDefinition { function, .. } if *function == f
+    ));
+    match &body.entries[2].node {
+        HugTreeEntry::ModuleDefinition { module, body } => {
+            assert_eq!(*module, inner);
+            assert_eq!(body.idents, vec![y]);
+            assert!(matches!(
+                &body.entries[0].node,
+                HugTreeEntry::VariableDefinition { variable, .. } if *variable == y
+            ));
+        }
+        other => panic!("Expected a module definition, got {:?}", other),
+    }
+    assert!(tree.unresolved.is_empty());
+}
+
+#[test]
+fn scoped_use() {
+    let tree = parse("module m { use std.io }");
+    let (m, std, io) = (Ident(0), Ident(1), Ident(2));
+
+    assert_eq!(tree.root().idents, vec![m]);
+    match &tree.root().entries[0].node {
+        HugTreeEntry::ModuleDefinition { module, body } => {
+            assert_eq!(*module, m);
+            assert_eq!(body.idents, vec![io]);
+            assert!(matches!(
+                &body.entries[0].node,
+                HugTreeEntry::Use { path } if *path == [std, io]
+            ));
+        }
+        other => panic!("Expected a module definition, got {:?}", other),
+    }
+    assert_eq!(tree.unresolved, vec![std]);
+
+    let tree = parse("use io");
+    assert_eq!(tree.root().idents, vec![Ident(0)]);
+
+    assert!(matches!(
+        HugTree::from_token_pairs(lex("use std.5")),
+        Err(ParseError::UnexpectedToken(_))
+    ));
+}
+
+#[test]
+fn return_statement() {
+    let tree = parse("function f(a) { if a { return } return a + 1 } let b = 2");
+    assert_eq!(tree.root().entries.len(), 2);
+    assert!(!tree
+        .root()
+        .entries
+        .iter()
+        .any(|entry| matches!(entry.node, HugTreeEntry::Return(_))));
+
+    let body = match &tree.root().entries[0].node {
+        HugTreeEntry::FunctionDefinition { body, .. } => body,
+        other => panic!("Expected a function definition, got {:?}", other),
+    };
+    match &body.entries[0].node {
+        HugTreeEntry::Expression(Expression::If { then_branch, .. }) => {
+            assert!(matches!(
+                then_branch.entries[0].node,
+                HugTreeEntry::Return(None)
+            ))
+        }
+        other => panic!("Expected an if, got {:?}", other),
+    }
+    assert!(matches!(
+        &body.entries[1].node,
+        HugTreeEntry::Return(Some(value)) if grouping(value) == "(v1 Add 1)"
+    ));
+
+    let tree = parse("function f() { return let x = 1 }");
+    match &tree.root().entries[0].node {
+        HugTreeEntry::FunctionDefinition { body, .. } => {
+            assert!(matches!(body.entries[0].node, HugTreeEntry::Return(None)));
+            assert_eq!(body.entries.len(), 2);
+        }
+        other => panic!("Expected a function definition, got {:?}", other),
+    }
+    assert!(matches!(
+        parse("return").root().entries[..],
+        [Spanned {
+            node: HugTreeEntry::Return(None),
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn parse_errors() {
+    let error = |program| HugTree::from_token_pairs(lex(program)).unwrap_err();
+
+    match error("let x = 1 if x 5") {
+        ParseError::ExpectedToken { expected, found } => {
+            assert_eq!(expected, TokenKind::OpenBrace);
+            assert_eq!(found.text, "5");
+            assert_eq!(found.span, 15..16);
+        }
+        other => panic!("Expected an ExpectedToken error, got {:?}", other),
+    }
+    assert!(matches!(
+        error("function f(5) {}"),
+        ParseError::UnexpectedToken(pair) if pair.text == "5"
+    ));
+    assert!(matches!(
+        error("function 5() {}"),
+        ParseError::UnexpectedToken(_)
+    ));
+    assert!(matches!(
+        error("@custom(5 = 1) function f() {}"),
+        ParseError::UnexpectedToken(_)
+    ));
+    assert!(matches!(
+        error("@custom(a = b) function f() {}"),
+        ParseError::UnexpectedToken(_)
+    ));
+    assert!(matches!(error("$"), ParseError::UnexpectedToken(_)));
+    assert_eq!(error("type Point"), ParseError::UnexpectedEof);
+    assert_eq!(
+        error("function f( {").to_string(),
+        "unexpected `{` (at 12..13)"
+    );
+
+    assert!(HugTree::from_token_pairs(lex("@extern(other = \"x\") function f()")).is_ok());
+}
+
+#[test]
+fn string_folding() {
+    let expression = |program| HugTreeParser::parse_expression_only(lex(program)).unwrap();
+
+    assert!(matches!(
+        expression("\"a\" + \"b\" + \"c\""),
+        Expression::Literal(HugValue::String(v)) if v == "abc"
+    ));
+    assert!(matches!(
+        expression("x + (\"a\" + \"b\")"),
+        Expression::BinaryOp { rhs, .. } if matches!(&rhs.node, Expression::Literal(HugValue::String(v)) if v == "ab")
+    ));
+    assert!(matches!(
+        expression("\"a\" + x + \"b\""),
+        Expression::BinaryOp { .. }
+    ));
+    assert!(matches!(
+        expression("\"a\" + 1"),
+        Expression::BinaryOp { .. }
+    ));
+}
+
+const RENAME_PROGRAM: &str = r###"
+let a = 1
+let b = 2
+function f(a) {
+    a
+}
+function g() {
+    a = 3
+    print(a + b)
+}
+"###;
+
+#[test]
+fn complete() {
+    let program = "let count = 1 let color = 2 let co = 3 let x = 4";
+    let mut tokenizer = Tokenizer::new(program);
+    let tokens = tokenizer.tokenize();
+    let pairs = generate_pairs(program, tokens);
+    let id = |name: &str| tokenizer.idents[name];
+
+    let tree = HugTree::from_token_pairs(pairs.clone()).unwrap();
+    let root = tree.root();
+    assert_eq!(
+        root.complete("co", &tokenizer.idents),
+        vec![id("co"), id("color"), id("count")]
+    );
+    assert_eq!(root.complete("cou", &tokenizer.idents), vec![id("count")]);
+    assert!(root.complete("y", &tokenizer.idents).is_empty());
+    assert_eq!(root.complete("", &tokenizer.idents).len(), 4);
+
+    let mut parser = HugTreeParser::new(pairs);
+    parser.next_entry().unwrap();
+    parser.next_entry().unwrap();
+    assert_eq!(
+        parser.complete("co", &tokenizer.idents),
+        vec![id("color"), id("count")]
+    );
+}
+
+#[test]
+fn lookup() {
+    let program = "let x = 1 let y = 2 function f() { let y = 3 let z = 4 }";
+    let mut tokenizer = Tokenizer::new(program);
+    let tokens = tokenizer.tokenize();
+    let pairs = generate_pairs(program, tokens);
+    let id = |name: &str| tokenizer.idents[name];
+    let (x, y, z) = (id("x"), id("y"), id("z"));
+
+    let tree = HugTree::from_token_pairs(pairs.clone()).unwrap();
+    let root = tree.root();
+    let body = match &root.entries[2].node {
+        HugTreeEntry::FunctionDefinition { body, .. } => body,
+        other => panic!("Expected a function definition, got {:?}", other),
+    };
+    assert_eq!(body.lookup(x, &[root]), Some(&HugValue::from(1)));
+    assert_eq!(body.lookup(y, &[root]), Some(&HugValue::from(3)));
+    assert_eq!(body.lookup(z, &[root]), Some(&HugValue::from(4)));
+    assert_eq!(body.lookup(x, &[]), None);
+    assert_eq!(root.lookup(z, &[]), None);
+
+    let mut parser = HugTreeParser::new(pairs);
+    parser.next_entry().unwrap();
+    assert_eq!(parser.lookup(x), Some(&HugValue::from(1)));
+    assert_eq!(parser.lookup(y), None);
+}
+
+#[test]
+fn rename_ident() {
+    let mut tokenizer = Tokenizer::new(RENAME_PROGRAM);
+    let tokens = tokenizer.tokenize();
+    let mut idents = tokenizer.idents;
+    let tree = HugTree::from_token_pairs(generate_pairs(RENAME_PROGRAM, tokens)).unwrap();
+    let mut root = tree.root().clone();
+
+    let (a, b) = (idents["a"], idents["b"]);
+    let renamed = root.rename_ident(&mut idents, a, "c").unwrap();
+    assert_eq!(idents["c"], renamed);
+    assert!(root.is_declared(renamed));
+    assert!(!root.is_declared(a));
+    assert!(matches!(root.resolve(renamed), Some(HugValue::Int32(1))));
+
+    // The argument `a` of `f` is a different variable and keeps its name
+    match &root.entries[2].node {
+        HugTreeEntry::FunctionDefinition { args, body, .. } => {
+            assert_eq!(args, &vec![a]);
+            assert!(matches!(body.value(), Some(Expression::Variable(id)) if *id == a));
+        }
+        other => panic!("Expected a function, got {:?}", other),
+    }
+    match &root.entries[3].node {
+        HugTreeEntry::FunctionDefinition { body, .. } => {
+            assert!(matches!(
+                &body.entries[0].node,
+                HugTreeEntry::Assignment { variable, .. } if *variable == renamed
+            ));
+            assert!(matches!(
+                &body.entries[1].node,
+                HugTreeEntry::FunctionCall { args, .. } if matches!(
+                    args[0].expression(),
+                    Expression::BinaryOp { lhs, .. } if matches!(lhs.node, Expression::Variable(id) if id == renamed)
+                )
+            ));
+        }
+        other => panic!("Expected a function, got {:?}", other),
+    }
+
+    assert_eq!(
+        root.rename_ident(&mut idents, renamed, "b"),
+        Err(RenameError::Conflict(b))
+    );
+    assert_eq!(
+        root.rename_ident(&mut idents, renamed, "let"),
+        Err(RenameError::InvalidName("let".to_string()))
+    );
+    assert_eq!(
+        root.rename_ident(&mut idents, renamed, "1c"),
+        Err(RenameError::InvalidName("1c".to_string()))
+    );
+    assert_eq!(root.rename_ident(&mut idents, renamed, "c"), Ok(renamed));
+}
+
+#[test]
+fn array_literals() {
+    let expression = |program| HugTreeParser::parse_expression_only(lex(program));
+
+    assert!(matches!(
+        expression("[]"),
+        Ok(Expression::ArrayLiteral(elements)) if elements.is_empty()
+    ));
+    assert!(matches!(
+        expression("[1, 2, 3,]"),
+        Ok(Expression::ArrayLiteral(elements)) if elements.len() == 3
+    ));
+    match expression("[[1], [2, x]]").unwrap() {
+        Expression::ArrayLiteral(elements) => {
+            assert!(matches!(&elements[0], Expression::ArrayLiteral(inner) if inner.len() == 1));
+            assert!(matches!(
+                &elements[1],
+                Expression::ArrayLiteral(inner) if matches!(inner[1], Expression::Variable(Ident(0)))
+            ));
+        }
+        other => panic!("Expected an array, got {:?}", other),
+    }
+
+    let nested = expression("[[1], [2 * 3], []]").unwrap();
+    assert!(nested.is_constant());
+    assert_eq!(
+        nested.get_constant_value().unwrap().to_string(),
+        "[[1], [6], []]"
+    );
+    assert!(!expression("[1, x]").unwrap().is_constant());
+
+    assert!(matches!(
+        expression("[,]"),
+        Err(ParseError::UnexpectedToken(_))
+    ));
+    assert!(matches!(
+        expression("[1 2]"),
+        Err(ParseError::UnexpectedToken(_))
+    ));
+    assert_eq!(expression("[1,").unwrap_err(), ParseError::UnexpectedEof);
+
+    let tree = parse("[1, 2]");
+    assert!(matches!(
+        &tree.root().entries[0].node,
+        HugTreeEntry::Expression(Expression::ArrayLiteral(_))
+    ));
+}
+
+#[test]
+fn index_expressions() {
+    let expression = |program| HugTreeParser::parse_expression_only(lex(program)).unwrap();
+
+    assert!(matches!(
+        expression("a[1]"),
+        Expression::Index { base, index }
+            if matches!(base.node, Expression::Variable(Ident(0)))
+                && matches!(index.node, Expression::Literal(HugValue::Int32(1)))
+    ));
+    match expression("a[i + 1]") {
+        Expression::Index { base, index } => {
+            assert!(matches!(base.node, Expression::Variable(Ident(0))));
+            assert_eq!(grouping(&index), "(v1 Add 1)");
+        }
+        other => panic!("Expected an index expression, got {:?}", other),
+    }
+    assert!(matches!(
+        expression("a[0][1].b"),
+        Expression::FieldAccess { base, .. } if matches!(
+            &base.node,
+            Expression::Index { base, .. } if matches!(base.node, Expression::Index { .. })
+        )
+    ));
+
+    let variables =
+        |id| (id == Ident(0)).then(|| HugValue::from(vec![HugValue::from(10), HugValue::from(20)]));
+    assert!(matches!(
+        expression("a[i + 1]").evaluate(|id| match id {
+            Ident(1) => Some(HugValue::from(0)),
+            id => variables(id),
+        }),
+        Ok(HugValue::Int32(20))
+    ));
+    assert_eq!(
+        expression("a[2]").evaluate(variables).unwrap_err(),
+        EvalError::IndexOutOfBounds {
+            index: "2".to_string(),
+            len: 2
+        }
+    );
+    assert!(matches!(
+        expression("\"abc\"[1]").get_constant_value(),
+        Ok(HugValue::String(c)) if c == "b"
+    ));
+
+    assert!(matches!(
+        HugTreeParser::parse_expression_only(lex("a[1")),
+        Err(ParseError::UnexpectedEof)
+    ));
+    let tree = parse("let a = 1 a[0]");
+    assert!(matches!(
+        &tree.root().entries[1].node,
+        HugTreeEntry::Expression(Expression::Index { .. })
+    ));
+}
+
+#[test]
+fn map_index_expressions() {
+    let expression = |program| HugTreeParser::parse_expression_only(lex(program)).unwrap();
+    let map = HugValue::from(HashMap::from([("a".to_string(), HugValue::from(1))]));
+    let variables = |id| (id == Ident(0)).then(|| map.clone());
+
+    assert_eq!(
+        expression("m[\"a\"]").evaluate(variables),
+        Ok(HugValue::Int32(1))
+    );
+    assert_eq!(
+        expression("m[\"b\"]").evaluate(variables),
+        Err(EvalError::MissingKey("b".to_string()))
+    );
+    assert_eq!(
+        expression("m[0]").evaluate(variables),
+        Err(EvalError::TypeError(TypeError::new(
+            "String",
+            &HugValue::from(0)
+        )))
+    );
+
+    let options = EvalOptions {
+        missing_key_is_unit: true,
+    };
+    assert_eq!(
+        expression("m[\"b\"]").evaluate_with_options(variables, options),
+        Ok(HugValue::Unit)
+    );
+    assert_eq!(
+        expression("m[\"a\"]").evaluate_with_options(variables, options),
+        Ok(HugValue::Int32(1))
+    );
+}
+
+#[test]
+fn unary_operators() {
+    let expression = |program| HugTreeParser::parse_expression_only(lex(program)).unwrap();
+
+    assert!(matches!(
+        expression("-5"),
+        Expression::UnaryOp { op: UnaryOperator::Negate, operand }
+            if matches!(operand.node, Expression::Literal(HugValue::Int32(5)))
+    ));
+    assert!(matches!(
+        expression("!true"),
+        Expression::UnaryOp { op: UnaryOperator::Not, operand }
+            if matches!(operand.node, Expression::Literal(HugValue::Bool(true)))
+    ));
+    assert!(matches!(
+        expression("-a * b"),
+        Expression::BinaryOp { op: BinaryOperator::Multiply, lhs, .. }
+            if matches!(lhs.node, Expression::UnaryOp { .. })
+    ));
+    assert!(matches!(
+        expression("!!a.b"),
+        Expression::UnaryOp { operand, .. } if matches!(
+            &operand.node,
+            Expression::UnaryOp { operand, .. } if matches!(operand.node, Expression::FieldAccess { .. })
+        )
+    ));
+    assert!(matches!(
+        expression("1 - -2"),
+        Expression::BinaryOp { op: BinaryOperator::Subtract, rhs, .. }
+            if matches!(rhs.node, Expression::UnaryOp { op: UnaryOperator::Negate, .. })
+    ));
+
+    assert!(matches!(
+        expression("-5 + 2").get_constant_value(),
+        Ok(HugValue::Int32(-3))
+    ));
+    assert!(matches!(
+        expression("!(1 < 2)").get_constant_value(),
+        Ok(HugValue::Bool(false))
+    ));
+    assert_eq!(
+        expression("!1").get_constant_value().unwrap_err(),
+        EvalError::TypeError(TypeError {
+            expected: "Bool",
+            found: TypeKind::Int32
+        })
+    );
+
+    let unsigned = Expression::UnaryOp {
+        op: UnaryOperator::Negate,
+        operand: Box::new(Spanned::new(Expression::Literal(HugValue::from(5u8)), 0..0)),
+    };
+    assert_eq!(
+        unsigned.get_constant_value().unwrap_err(),
+        EvalError::TypeError(TypeError {
+            expected: "a signed number",
+            found: TypeKind::UInt8
+        })
+    );
+}
+
+#[test]
+fn type_of() {
+    let expression = |program| HugTreeParser::parse_expression_only(lex(program)).unwrap();
+
+    assert!(matches!(
+        expression("typeof x"),
+        Expression::TypeOf(value) if matches!(value.node, Expression::Variable(Ident(0)))
+    ));
+    assert!(matches!(
+        expression("typeof x == \"Int32\""),
+        Expression::BinaryOp { op: BinaryOperator::IsEqualTo, lhs, .. }
+            if matches!(lhs.node, Expression::TypeOf(_))
+    ));
+
+    let type_name = |program| match expression(program).get_constant_value() {
+        Ok(HugValue::String(name)) => name,
+        other => panic!("Expected a type name, got {:?}", other),
+    };
+    assert_eq!(type_name("typeof 5"), "Int32");
+    assert_eq!(type_name("typeof \"a\" + 1"), "String1");
+    assert_eq!(type_name("typeof [1]"), "Array");
+    assert_eq!(type_name("typeof typeof true"), "String");
+
+    let tree = parse("let x = 1.5 typeof x");
+    match &tree.root().entries[1].node {
+        HugTreeEntry::Expression(value) => assert!(matches!(
+            value.evaluate(|_| Some(HugValue::from(1.5f32))),
+            Ok(HugValue::String(name)) if name == "Float32"
+        )),
+        other => panic!("Expected an expression, got {:?}", other),
+    }
+}
+
+#[test]
+fn bitwise_operators() {
+    let expression = |program| HugTreeParser::parse_expression_only(lex(program)).unwrap();
+    let parse_grouping = |program| grouping(&expression(program));
+    let evaluate = |program| expression(program).get_constant_value();
+
+    assert_eq!(
+        parse_grouping("a | b ^ c & d << 1 + 2"),
+        "(v0 BitOr (v1 BitXor (v2 BitAnd (v3 ShiftLeft (1 Add 2)))))"
+    );
+    assert_eq!(
+        parse_grouping("a & 1 == 0 && b"),
+        "(((v0 BitAnd 1) IsEqualTo 0) And v1)"
+    );
+
+    assert!(matches!(evaluate("0xF0 & 0x0F"), Ok(HugValue::Int32(0))));
+    assert!(matches!(evaluate("0xF0 | 0x0F"), Ok(HugValue::Int32(0xFF))));
+    assert!(matches!(evaluate("0xFF ^ 0x0F"), Ok(HugValue::Int32(0xF0))));
+    assert!(matches!(evaluate("1 << 4"), Ok(HugValue::Int32(16))));
+    assert!(matches!(evaluate("-32 >> 2"), Ok(HugValue::Int32(-8))));
+    assert!(matches!(evaluate("~0"), Ok(HugValue::Int32(-1))));
+    assert!(matches!(evaluate("true ^ true"), Ok(HugValue::Bool(false))));
+
+    assert_eq!(
+        evaluate("1.5 & 1.5").unwrap_err(),
+        EvalError::TypeError(TypeError {
+            expected: "an integer or bool",
+            found: TypeKind::Float32
+        })
+    );
+    assert_eq!(
+        evaluate("~\"a\"").unwrap_err(),
+        EvalError::TypeError(TypeError {
+            expected: "an integer",
+            found: TypeKind::String
+        })
+    );
+}
+
+const TYPE_PROGRAM: &str = r###"
+type Point {
+    x: Int32,
+    y: Int32,
+}
+
+type Line { from: Point, to: Point }
+type Empty {}
+"###;
+
+#[test]
+fn type_definitions() {
+    let mut tokenizer = Tokenizer::new(TYPE_PROGRAM);
+    let tokens = tokenizer.tokenize();
+    let tree = HugTree::from_token_pairs(generate_pairs(TYPE_PROGRAM, tokens)).unwrap();
+    let root = tree.root();
+    let id = |name: &str| tokenizer.idents[name];
+    let (point, x, y) = (id("Point"), id("x"), id("y"));
+    let (line, from, to) = (id("Line"), id("from"), id("to"));
+
+    assert!(matches!(
+        &root.entries[0].node,
+        HugTreeEntry::TypeDefinition { name, fields, .. }
+            if *name == point && *fields == [(x, TypeKind::Int32), (y, TypeKind::Int32)]
+    ));
+    assert!(matches!(
+        &root.entries[1].node,
+        HugTreeEntry::TypeDefinition { name, fields, .. } if *name == line && *fields == [
+            (from, TypeKind::Other(point)),
+            (to, TypeKind::Other(point)),
+        ]
+    ));
+    assert!(matches!(
+        &root.entries[2].node,
+        HugTreeEntry::TypeDefinition { fields, .. } if fields.is_empty()
+    ));
+    assert!(root.is_declared(point) && root.is_declared(line));
+    assert!(tree.unresolved.is_empty());
+
+    let error = |program| HugTree::from_token_pairs(lex(program)).unwrap_err();
+    assert!(matches!(
+        error("type Point { x Int32 }"),
+        ParseError::ExpectedToken {
+            expected: TokenKind::Colon,
+            ..
+        }
+    ));
+    assert!(matches!(
+        error("type Point { x: Int32 y: Int32 }"),
+        ParseError::UnexpectedToken(pair) if pair.text == "y"
+    ));
+    assert!(matches!(
+        error("type Point { x: Int32,, }"),
+        ParseError::UnexpectedToken(pair) if pair.text == ","
+    ));
+}
+
+const STRUCT_PROGRAM: &str = r###"
+type Point { x: Int32, y: Int32 }
+let p = 0
+let q = 0
+p = Point { y: 1 + 1, x: 1, }
+q = p.y
+"###;
+
+#[test]
+fn struct_literals() {
+    let mut tokenizer = Tokenizer::new(STRUCT_PROGRAM);
+    let tokens = tokenizer.tokenize();
+    let tree = HugTree::from_token_pairs(generate_pairs(STRUCT_PROGRAM, tokens)).unwrap();
+    let id = |name: &str| tokenizer.idents[name];
+    let (point, x, y, p) = (id("Point"), id("x"), id("y"), id("p"));
+
+    let value = |i: usize| match &tree.root().entries[i].node {
+        HugTreeEntry::Assignment { value, .. } => value.clone(),
+        other => panic!("Expected an assignment, got {:?}", other),
+    };
+    let point_value = HugValue::Struct {
+        type_name: point,
+        fields: HashMap::from([(x, HugValue::from(1)), (y, HugValue::from(2))]),
+    };
+    assert!(value(3).is_constant());
+    assert_eq!(value(3).get_constant_value(), Ok(point_value.clone()));
+    assert_eq!(point_value.kind(), TypeKind::Other(point));
+    assert_eq!(
+        value(4).evaluate(|v| (v == p).then(|| point_value.clone())),
+        Ok(HugValue::from(2))
+    );
+    assert_eq!(
+        Expression::FieldAccess {
+            base: Box::new(Spanned::new(Expression::Literal(point_value), 0..0)),
+            field: p,
+        }
+        .evaluate(|_| None),
+        Err(EvalError::UnknownField(p))
+    );
+
+    let error = |program| HugTree::from_token_pairs(lex(program)).unwrap_err();
+    let (point, x, y, z) = (Ident(0), Ident(1), Ident(3), Ident(5));
+    assert_eq!(
+        error("type Point { x: Int32, y: Int32 } let p = 0 p = Point { x: 1, z: 2 }"),
+        ParseError::UnknownField {
+            type_name: point,
+            field: z
+        }
+    );
+    assert_eq!(
+        error("type Point { x: Int32, y: Int32 } let p = 0 p = Point { x: 1, x: 2 }"),
+        ParseError::UnknownField {
+            type_name: point,
+            field: x
+        }
+    );
+    assert_eq!(
+        error("type Point { x: Int32, y: Int32 } let p = 0 p = Point { x: 1 }"),
+        ParseError::MissingField {
+            type_name: point,
+            field: y
+        }
+    );
+}
+
+#[test]
+fn enum_definitions() {
+    let program =
+        "enum Color { Red, Green, Blue, }\nenum Empty {}\nenum Flag { A = 1, B = 1 << 2, C }";
+    let mut tokenizer = Tokenizer::new(program);
+    let tokens = tokenizer.tokenize();
+    let tree = HugTree::from_token_pairs(generate_pairs(program, tokens)).unwrap();
+    let root = tree.root();
+    let id = |name: &str| tokenizer.idents[name];
+
+    match &root.entries[0].node {
+        HugTreeEntry::EnumDefinition { name, variants, .. } => {
+            assert_eq!(*name, id("Color"));
+            let names: Vec<Ident> = variants.iter().map(|(variant, _)| *variant).collect();
+            assert_eq!(names, vec![id("Red"), id("Green"), id("Blue")]);
+            assert!(variants.iter().all(|(_, value)| value.is_none()));
+        }
+        other => panic!("Expected an enum definition, got {:?}", other),
+    }
+    assert!(matches!(
+        &root.entries[1].node,
+        HugTreeEntry::EnumDefinition { variants, .. } if variants.is_empty()
+    ));
+    match &root.entries[2].node {
+        HugTreeEntry::EnumDefinition { variants, .. } => {
+            assert_eq!(variants[0].0, id("A"));
+            assert_eq!(variants[0].1.as_ref().unwrap().assert::<i32>(), Ok(1));
+            assert_eq!(variants[1].1.as_ref().unwrap().assert::<i32>(), Ok(4));
+            assert_eq!(variants[2].0, id("C"));
+            assert!(variants[2].1.is_none());
+        }
+        other => panic!("Expected an enum definition, got {:?}", other),
+    }
+    assert!(root.is_declared(id("Color")) && root.is_declared(id("Flag")));
+    assert!(tree.unresolved.is_empty());
+
+    let error = |program| HugTree::from_token_pairs(lex(program)).unwrap_err();
+    assert!(matches!(
+        error("enum Color { Red Green }"),
+        ParseError::UnexpectedToken(pair) if pair.text == "Green"
+    ));
+    assert!(matches!(
+        error("let x = 1 enum Color { Red = x }"),
+        ParseError::InvalidDiscriminant {
+            error: EvalError::NonConstant,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn visibility() {
+    let tree = parse(
+        "public function f() { let a = 1 } function g() {} public let x = 1 let y = 2 \
+         public type T {} private enum E {}",
+    );
+    let visibility = |entry: &HugTreeEntry| match entry {
+        HugTreeEntry::FunctionDefinition { visibility, .. }
+        | HugTreeEntry::VariableDefinition { visibility, .. }
+        | HugTreeEntry::TypeDefinition { visibility, .. }
+        | HugTreeEntry::EnumDefinition { visibility, .. } => *visibility,
+        other => panic!("Expected a definition, got {:?}", other),
+    };
+
+    let entries = &tree.root().entries;
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| visibility(entry))
+            .collect::<Vec<_>>(),
+        vec![
+            Visibility::Public,
+            Visibility::Private,
+            Visibility::Public,
+            Visibility::Private,
+            Visibility::Public,
+            Visibility::Private,
+        ]
+    );
+    match &entries[0].node {
+        HugTreeEntry::FunctionDefinition { body, .. } => {
+            assert_eq!(visibility(&body.entries[0].node), Visibility::Private);
+        }
+        other => panic!("Expected a function, got {:?}", other),
+    }
+}
+
+#[test]
+fn link_trees() {
+    fn tree(idents: &mut HashMap<String, Ident>, program: &str) -> HugTree {
+        let mut tokenizer = Tokenizer::with_idents(std::mem::take(idents), program);
+        let tokens = tokenizer.tokenize();
+        *idents = tokenizer.idents;
+        HugTree::from_token_pairs(generate_pairs(program, tokens)).unwrap()
+    }
+
+    let mut idents = HashMap::new();
+    let library = tree(&mut idents, "function helper(x) { return x }");
+    let main = tree(&mut idents, "function main() { helper(1) } main()");
+    let conflicting = tree(&mut idents, "let helper = 1");
+    let id = |name: &str| idents[name];
+
+    assert_eq!(main.unresolved, vec![id("helper")]);
+    let linked = link(vec![library, main]).unwrap();
+    assert!(linked.unresolved.is_empty());
+    assert_eq!(linked.functions.len(), 2);
+    assert_eq!(linked.functions[1].function, id("main"));
+    assert!(matches!(
+        linked.root().members.get(&id("main")),
+        Some(HugValue::Function(1))
+    ));
+    assert!(matches!(
+        linked.root().members.get(&id("helper")),
+        Some(HugValue::Function(0))
+    ));
+    assert_eq!(linked.root().entries.len(), 3);
+
+    let library = tree(&mut idents.clone(), "function helper(x) { return x }");
+    assert_eq!(
+        link(vec![library, conflicting]).unwrap_err(),
+        LinkError::Conflict(id("helper"))
+    );
+}
+
+#[test]
+fn outline_mode() {
+    let program = "function f(a, b = 1) -> Int32 { let x = 1 if x { g(x) } }\nfunction h() {}";
+    let options = ParserOptions {
+        outline: true,
+        ..ParserOptions::default()
+    };
+    let tree = HugTreeParser::with_options(lex(program), options)
+        .parse()
+        .unwrap();
+
+    match &tree.root().entries[0].node {
+        HugTreeEntry::FunctionDefinition {
+            args,
+            return_type,
+            body,
+            ..
+        } => {
+            assert_eq!(args.len(), 2);
+            assert_eq!(*return_type, Some(TypeKind::Int32));
+            assert!(body.entries.is_empty());
+        }
+        other => panic!("Expected a function, got {:?}", other),
+    }
+    assert!(tree.functions[0].args[1].default.is_some());
+    assert!(tree.unresolved.is_empty());
+
+    let span = tree.skipped_bodies[0].span();
+    assert_eq!(*tree.skipped_bodies[0], 0);
+    assert_eq!(&program[span.clone()], " let x = 1 if x { g(x) } ");
+    assert_eq!(*tree.skipped_bodies[1], 1);
+    assert!(program[tree.skipped_bodies[1].span()].is_empty());
+    assert_eq!(parse(&program[span]).root().entries.len(), 2);
+
+    assert!(parse(program).skipped_bodies.is_empty());
+    assert_eq!(
+        HugTreeParser::with_options(lex("function f() { {"), options)
+            .parse()
+            .unwrap_err(),
+        ParseError::UnexpectedEof
+    );
+}
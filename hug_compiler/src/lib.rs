@@ -1 +1 @@
-pub fn compile(program: String) {}
+pub fn compile(_program: String) {}
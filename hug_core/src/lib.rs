@@ -9,7 +9,7 @@ pub fn init(module: &mut HugModule) {
     println!("HUG CORE LOADED!!!!");
 }
 
-pub fn deinit(module: &mut HugModule) {
+pub fn deinit(_module: &mut HugModule) {
     println!("rip hug");
 }
 
@@ -21,6 +21,7 @@ fn add(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
     Some(HugValue::from(left + right))
 }
 
+/// Prints the first argument with every `{}` in it replaced by the next argument.
 fn print(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
     let fmt = args
         .next()
@@ -28,8 +29,17 @@ fn print(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
         .assert::<String>()
         .expect("First argument of print must be a string!");
 
-    let fmt_args = args.collect::<Vec<HugValue>>();
-
+    let mut pieces = fmt.split("{}");
+    let mut output = pieces.next().unwrap_or_default().to_string();
+    for piece in pieces {
+        match args.next() {
+            Some(arg) => output.push_str(&format!("{:?}", arg)),
+            None => output.push_str("{}"),
+        }
+        output.push_str(piece);
+    }
+
+    println!("{}", output);
     None
 }
 
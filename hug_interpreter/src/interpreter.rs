@@ -0,0 +1,157 @@
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use hug_ast::{
+    expression::{CallArg, Expression},
+    HugScope, HugTree, HugTreeEntry,
+};
+use hug_lib::{
+    error::{CallError, EvalError, TypeError},
+    value::{HugExternalFunction, HugValue},
+    Ident,
+};
+
+/// Errors that can occur while running a tree with [`Interpreter::run`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    Eval(EvalError),
+    /// A function call failed, like calling a value that isn't a function.
+    Call {
+        function: Ident,
+        error: CallError,
+    },
+    /// An entry that the interpreter can't run yet.
+    Unsupported(&'static str),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::Eval(e) => write!(f, "{}", e),
+            RuntimeError::Call { function, error } => {
+                write!(f, "could not call {:?}: {}", function, error)
+            }
+            RuntimeError::Unsupported(what) => write!(f, "{} are not supported yet", what),
+        }
+    }
+}
+
+impl Error for RuntimeError {}
+
+impl From<EvalError> for RuntimeError {
+    fn from(e: EvalError) -> RuntimeError {
+        RuntimeError::Eval(e)
+    }
+}
+
+impl From<TypeError> for RuntimeError {
+    fn from(e: TypeError) -> RuntimeError {
+        RuntimeError::Eval(EvalError::TypeError(e))
+    }
+}
+
+/// Runs a [`HugTree`] by walking its entries, unlike [`HugVM`](crate::vm::HugVM) it doesn't
+/// load any scripts or modules itself. Functions the program calls have to be registered
+/// with [`Interpreter::register`] first, as functions defined in the script can't be called
+/// yet. Calls are only run as statements, a call inside an expression gives
+/// [`EvalError::NonConstant`].
+#[derive(Debug, Default)]
+pub struct Interpreter {
+    variables: HashMap<Ident, HugValue>,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter::default()
+    }
+
+    /// Makes the host function `function` available to programs as `id`.
+    pub fn register(&mut self, id: Ident, function: HugExternalFunction) {
+        self.set_variable(id, HugValue::ExternalFunction(function));
+    }
+
+    pub fn get_variable(&self, id: Ident) -> Option<&HugValue> {
+        self.variables.get(&id)
+    }
+
+    pub fn set_variable(&mut self, id: Ident, value: HugValue) {
+        self.variables.insert(id, value);
+    }
+
+    /// Runs every entry of the root scope of `tree` in order. Returns the value of the first
+    /// `return` that is reached, or `None` if the program ran to its end. Variables keep their
+    /// values after running, so the next tree can use them.
+    pub fn run(&mut self, tree: &HugTree) -> Result<Option<HugValue>, RuntimeError> {
+        self.run_scope(tree.root())
+    }
+
+    fn run_scope(&mut self, scope: &HugScope) -> Result<Option<HugValue>, RuntimeError> {
+        for entry in scope.entries.iter() {
+            match &entry.node {
+                HugTreeEntry::VariableDefinition {
+                    variable, value, ..
+                } => self.set_variable(*variable, value.clone()),
+                HugTreeEntry::Assignment { variable, value } => {
+                    let value = value.evaluate(|id| self.get_variable(id).cloned())?;
+                    self.set_variable(*variable, value);
+                }
+                HugTreeEntry::FunctionCall { function, args } => {
+                    let args = self.evaluate_args(args)?;
+                    self.get_variable(*function)
+                        .ok_or(EvalError::UndefinedVariable(*function))?
+                        .call_external(args)
+                        .map_err(|error| RuntimeError::Call {
+                            function: *function,
+                            error,
+                        })?;
+                }
+                HugTreeEntry::Expression(Expression::If { .. }) => {
+                    return Err(RuntimeError::Unsupported("if expressions"))
+                }
+                HugTreeEntry::Expression(expression) => {
+                    expression.evaluate(|id| self.get_variable(id).cloned())?;
+                }
+                HugTreeEntry::Return(value) => {
+                    let value = match value {
+                        Some(value) => value.evaluate(|id| self.get_variable(id).cloned())?,
+                        None => HugValue::Unit,
+                    };
+                    return Ok(Some(value));
+                }
+                HugTreeEntry::While { .. } => return Err(RuntimeError::Unsupported("loops")),
+                HugTreeEntry::ModuleDefinition { .. } => {
+                    return Err(RuntimeError::Unsupported("modules"))
+                }
+                // Definitions that don't run anything
+                HugTreeEntry::ExternalTypeDefinition { .. }
+                | HugTreeEntry::TypeDefinition { .. }
+                | HugTreeEntry::EnumDefinition { .. }
+                | HugTreeEntry::ExternalModuleDefinition { .. }
+                | HugTreeEntry::ExternalFunctionDefinition { .. }
+                | HugTreeEntry::FunctionDefinition { .. }
+                | HugTreeEntry::Use { .. } => (),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Evaluates call arguments, spreading the elements of spread arguments into the list.
+    fn evaluate_args(&self, args: &[CallArg]) -> Result<Vec<HugValue>, RuntimeError> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            let value = arg
+                .expression()
+                .evaluate(|id| self.get_variable(id).cloned())?;
+            match (arg, value) {
+                (CallArg::Spread(_), HugValue::Array(array)) => values.extend(array),
+                (CallArg::Spread(_), other) => return Err(TypeError::new("Array", &other).into()),
+                (CallArg::Positional(_), value) => values.push(value),
+                (CallArg::Named(..), _) => {
+                    return Err(RuntimeError::Unsupported(
+                        "named arguments of external functions",
+                    ))
+                }
+            }
+        }
+        Ok(values)
+    }
+}
@@ -1,3 +1,2 @@
-use hug_ast::HugTree;
-
+pub mod interpreter;
 pub mod vm;
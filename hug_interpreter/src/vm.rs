@@ -1,10 +1,15 @@
 use std::{collections::HashMap, fs::OpenOptions, io::Read};
 
-use hug_ast::HugTree;
+use hug_ast::{
+    diagnostic::Diagnostic,
+    expression::{CallArg, Expression},
+    HugTree,
+};
 use hug_core::HUG_CORE_SCRIPT;
 use hug_lexer::{parser::generate_pairs, tokenizer::Tokenizer};
 use hug_lib::{
-    value::{HugExternalFunction, HugValue},
+    error::TypeError,
+    value::{Callee, HugValue},
     HugModule, Ident,
 };
 
@@ -47,7 +52,7 @@ impl HugVM {
         let mut file = OpenOptions::new()
             .read(true)
             .open(file_path)
-            .expect(format!("Could not open file {}!", file_path).as_str());
+            .unwrap_or_else(|_| panic!("Could not open file {}!", file_path));
 
         let mut buffer = String::new();
         file.read_to_string(&mut buffer)
@@ -65,7 +70,14 @@ impl HugVM {
         self.idents = tokenizer.idents;
 
         let pairs = generate_pairs(program, tokens);
-        let t = HugTree::from_token_pairs(pairs);
+        let t = HugTree::from_token_pairs(pairs)
+            .unwrap_or_else(|e| panic!("Could not parse script: {:?}", e));
+        for diagnostic in t.diagnostics.iter() {
+            eprintln!("{}", diagnostic.render(program));
+        }
+        if t.diagnostics.iter().any(Diagnostic::is_error) {
+            panic!("Could not parse script, see the errors above");
+        }
         self.tree.merge_with(t);
     }
 
@@ -89,15 +101,22 @@ impl HugVM {
             })
         }
 
-        while self.pointer < self.tree.entries.len() {
-            let instruction = self.tree.entries.get(self.pointer).unwrap().clone();
+        while self.pointer < self.tree.root().entries.len() {
+            let instruction = self
+                .tree
+                .root()
+                .entries
+                .get(self.pointer)
+                .unwrap()
+                .node
+                .clone();
 
             #[cfg(debug_assertions)]
             println!("Instruction: {:?}", instruction);
 
             match instruction {
-                hug_ast::HugTreeEntry::ModuleDefinition { module } => todo!(),
-                hug_ast::HugTreeEntry::ExternalModuleDefinition { module, location } => unsafe {
+                hug_ast::HugTreeEntry::ModuleDefinition { .. } => todo!(),
+                hug_ast::HugTreeEntry::ExternalModuleDefinition { location, .. } => unsafe {
                     let library = libloading::Library::new(location).unwrap();
                     let init_func: libloading::Symbol<unsafe extern "C" fn(&mut HugModule)> =
                         library
@@ -113,27 +132,39 @@ impl HugVM {
                         self.set_variable(id, HugValue::from(fun));
                     }
                 },
-                hug_ast::HugTreeEntry::VariableDefinition { variable, value } => {
+                hug_ast::HugTreeEntry::VariableDefinition {
+                    variable, value, ..
+                } => {
                     self.set_variable(variable, value.clone());
                 }
+                hug_ast::HugTreeEntry::Assignment { variable, value } => {
+                    let value = value
+                        .evaluate(|v| self.get_variable(v).cloned())
+                        .unwrap_or_else(|e| panic!("Could not evaluate {:?}: {}", value, e));
+                    self.set_variable(variable, value);
+                }
                 hug_ast::HugTreeEntry::FunctionCall { function, args } => {
-                    match self.get_variable(function).unwrap() {
-                        HugValue::ExternalFunction(f) => {
-                            f(args
-                                .iter()
-                                .map(|a| match a {
-                                    hug_ast::HugTreeFunctionCallArg::Variable(v) => {
-                                        self.get_variable(*v).unwrap().clone()
-                                    }
-                                    hug_ast::HugTreeFunctionCallArg::Value(v) => v.clone(),
-                                })
-                                .collect::<Vec<HugValue>>()
-                                .into_iter());
+                    match self.get_variable(function).unwrap().callee() {
+                        Ok(Callee::External(f)) => {
+                            f(self.evaluate_args(&args).into_iter());
                         }
-                        HugValue::Function(l) => {
-                            self.pointer = *l;
+                        Ok(Callee::Interpreted(l)) => {
+                            let (named, positional): (Vec<_>, Vec<_>) =
+                                args.into_iter().partition(|arg| arg.name().is_some());
+                            // Spread arguments only have a length once they are evaluated
+                            let arg_count = if positional.iter().any(CallArg::is_spread) {
+                                self.evaluate_args(&positional).len()
+                            } else {
+                                positional.len()
+                            };
+                            let named: Vec<_> = named.iter().filter_map(CallArg::name).collect();
+
+                            if let Err(e) = self.tree.check_named_call(l, arg_count, &named) {
+                                panic!("Could not call {:?}: {}", function, e);
+                            }
+                            self.pointer = l;
                         }
-                        _ => panic!("Not a function! {:?}", function),
+                        Err(e) => panic!("Could not call {:?}: {}", function, e),
                     }
                 }
                 _ => (),
@@ -142,6 +173,52 @@ impl HugVM {
         }
     }
 
+    fn evaluate_args(&self, args: &[CallArg]) -> Vec<HugValue> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            let value = self.evaluate(arg.expression());
+
+            match (arg, value) {
+                (CallArg::Spread(_), HugValue::Array(array)) => values.extend(array),
+                (CallArg::Spread(_), other) => {
+                    panic!(
+                        "Could not spread {:?}: {}",
+                        other,
+                        TypeError::new("Array", &other)
+                    )
+                }
+                (CallArg::Positional(_), value) => values.push(value),
+                (CallArg::Named(name, _), _) => {
+                    panic!("Could not pass {:?} by name to an external function", name)
+                }
+            }
+        }
+        values
+    }
+
+    /// Evaluates an expression, calling the external function directly when it is a call. Calls
+    /// to interpreted functions move the instruction pointer, so they can only be statements.
+    fn evaluate(&self, expression: &Expression) -> HugValue {
+        match expression {
+            Expression::Call { function, args } => {
+                match self.get_variable(*function).map(HugValue::callee) {
+                    Some(Ok(Callee::External(f))) => {
+                        f(self.evaluate_args(args).into_iter()).unwrap_or(HugValue::Unit)
+                    }
+                    Some(Ok(Callee::Interpreted(_))) => panic!(
+                        "Could not call {:?}: interpreted functions can't be called inside an expression",
+                        function
+                    ),
+                    Some(Err(e)) => panic!("Could not call {:?}: {}", function, e),
+                    None => panic!("Could not call {:?}: it isn't defined", function),
+                }
+            }
+            _ => expression
+                .evaluate(|v| self.get_variable(v).cloned())
+                .unwrap_or_else(|e| panic!("Could not evaluate {:?}: {}", expression, e)),
+        }
+    }
+
     #[inline]
     pub fn enforce_variables_len(&mut self, size: usize) {
         if self.variables.len() < size + 1 {
@@ -0,0 +1,82 @@
+use std::sync::Mutex;
+
+use hug_ast::HugTree;
+use hug_interpreter::interpreter::{Interpreter, RuntimeError};
+use hug_lexer::{lex, parser::generate_pairs, tokenizer::Tokenizer};
+use hug_lib::{
+    error::{CallError, EvalError},
+    value::HugValue,
+    Ident,
+};
+
+static PRINTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn print(args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+    let line: Vec<String> = args.map(|arg| arg.to_string()).collect();
+    PRINTED.lock().unwrap().push(line.join(" "));
+    None
+}
+
+fn run(program: &str) -> Result<Option<HugValue>, RuntimeError> {
+    Interpreter::new().run(&HugTree::from_token_pairs(lex(program)).unwrap())
+}
+
+#[test]
+fn straight_line_program() {
+    let program = "let x = 5\nx = x * 2\nprint(\"x is\", x)\nprint(...[x, x + 1])\nreturn x + 1";
+    let mut tokenizer = Tokenizer::new(program);
+    let tokens = tokenizer.tokenize();
+    let tree = HugTree::from_token_pairs(generate_pairs(program, tokens)).unwrap();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.register(tokenizer.idents["print"], print);
+    assert_eq!(interpreter.run(&tree), Ok(Some(HugValue::from(11))));
+    assert_eq!(
+        interpreter.get_variable(tokenizer.idents["x"]),
+        Some(&HugValue::from(10))
+    );
+    assert_eq!(*PRINTED.lock().unwrap(), ["x is 10", "10 11"]);
+}
+
+#[test]
+fn returns() {
+    assert_eq!(run("let x = 1"), Ok(None));
+    assert_eq!(run("let x = 1 return"), Ok(Some(HugValue::Unit)));
+    assert_eq!(
+        run("let x = 1 return x x = 2 return x"),
+        Ok(Some(HugValue::from(1)))
+    );
+}
+
+#[test]
+fn runtime_errors() {
+    assert_eq!(
+        run("missing(1)"),
+        Err(RuntimeError::Eval(EvalError::UndefinedVariable(Ident(0))))
+    );
+    assert_eq!(
+        run("let x = 1 x(2)"),
+        Err(RuntimeError::Call {
+            function: Ident(0),
+            error: CallError::NotCallable
+        })
+    );
+    assert_eq!(
+        run("let x = 1 x = x / 0"),
+        Err(RuntimeError::Eval(EvalError::DivideByZero))
+    );
+    assert_eq!(
+        run("let x = 0 while x < 1 { x = 1 }"),
+        Err(RuntimeError::Unsupported("loops"))
+    );
+    assert_eq!(
+        run("let x = true if x { x = false }"),
+        Err(RuntimeError::Unsupported("if expressions"))
+    );
+    assert_eq!(
+        run("missing(x = 1)"),
+        Err(RuntimeError::Unsupported(
+            "named arguments of external functions"
+        ))
+    );
+}
@@ -15,10 +15,7 @@ impl FilterUseless for Vec<TokenPair> {
         for pair in self {
             match pair.token.kind {
                 TokenKind::LineComment | TokenKind::BlockComment | TokenKind::Whitespace => (),
-                _ => new_self.push(TokenPair {
-                    text: pair.text.clone(),
-                    token: pair.token,
-                }),
+                _ => new_self.push(pair),
             }
         }
 
@@ -71,6 +68,21 @@ pub fn tokenize(program: &str) -> Vec<Token> {
     Tokenizer::new(program).tokenize()
 }
 
+/// Tokenizes `program` without whitespace and comments, handy for checking how operators
+/// are split up.
+pub fn tokenize_operators(program: &str) -> Vec<TokenKind> {
+    tokenize(program)
+        .into_iter()
+        .map(|token| token.kind)
+        .filter(|kind| {
+            !matches!(
+                kind,
+                TokenKind::LineComment | TokenKind::BlockComment | TokenKind::Whitespace
+            )
+        })
+        .collect()
+}
+
 pub fn run_test(program: &str, expected_result: &[(TokenKind, usize)]) {
     let tokens = tokenize(program);
     for (token, (expected_kind, expected_len)) in tokens.iter().zip(expected_result.iter()) {
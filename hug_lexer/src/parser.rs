@@ -1,47 +1,128 @@
-use hug_lib::value::HugValue;
+#[cfg(feature = "big-int")]
+use hug_lib::value::TypedDefinition;
+use hug_lib::{
+    span::Spanned,
+    value::{parse_hex_float, HugValue, TypeKind},
+};
 
-use crate::tokenizer::Token;
+use crate::tokenizer::{Base, LiteralKind, Token, TokenKind};
 
-#[derive(Debug, Clone)]
-pub struct TokenPair {
+/// A token along with where it is in the program, in bytes. The default pair is the null
+/// pair, used to signal that there are no more tokens left.
+pub type TokenPair = Spanned<Lexeme>;
+
+/// A token and the source text it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexeme {
+    /// The source text of the token exactly as it was written, so literals keep their base
+    /// prefix and underscores (`0xFF_00` stays `0xFF_00`).
     pub text: String,
     pub token: Token,
 }
 
-impl TokenPair {
+impl Default for Lexeme {
+    fn default() -> Lexeme {
+        Lexeme {
+            text: String::new(),
+            token: Token {
+                kind: TokenKind::Unknown,
+                len: 0,
+            },
+        }
+    }
+}
+
+impl Lexeme {
+    pub fn is_null(&self) -> bool {
+        self.token.len == 0 && self.token.kind == TokenKind::Unknown
+    }
+
+    /// Reads this pair as the name of a type, built-in types are recognized by their name and
+    /// any other identifier becomes a [`TypeKind::Other`].
+    pub fn expect_type(&self) -> Option<TypeKind> {
+        let id = self.token.kind.expect_ident()?;
+        Some(TypeKind::from_name(&self.text).unwrap_or(TypeKind::Other(id)))
+    }
+
     pub fn parse_literal(&self) -> Option<HugValue> {
-        if let Some(_) = self.token.kind.expect_literal() {
-            if let Ok(int) = self.text.parse::<i32>() {
-                Some(HugValue::from(int))
-            } else if let Ok(float) = self.text.parse::<f32>() {
-                Some(HugValue::from(float))
-            } else if self.text.len() > 2 {
-                Some(HugValue::from(String::from(
-                    &self.text[1..self.text.len() - 1],
-                )))
-            } else {
-                None
+        match self.token.kind.expect_literal()? {
+            LiteralKind::Boolean => Some(HugValue::Bool(self.text == "true")),
+            LiteralKind::Integer(base) => parse_integer(&self.text, base)
+                .map(HugValue::from)
+                .or_else(|| parse_wide_integer(&self.text, base)),
+            LiteralKind::Float(Base::Hexadecimal) => {
+                parse_hex_float(&self.text).map(HugValue::from)
             }
-        } else {
-            None
+            LiteralKind::Float(_) => self
+                .text
+                .replace('_', "")
+                .trim_end_matches('f')
+                .parse::<f32>()
+                .ok()
+                .map(HugValue::from),
+            _ if self.text.len() > 2 => Some(HugValue::from(String::from(
+                &self.text[1..self.text.len() - 1],
+            ))),
+            _ => None,
         }
     }
 }
 
+/// Strips the base prefix and any `_` separators from a numeric literal.
+fn literal_digits(text: &str, base: Base) -> String {
+    let digits = if base == Base::Decimal {
+        text
+    } else {
+        &text[2..]
+    };
+    digits.replace('_', "")
+}
+
+pub fn parse_integer(text: &str, base: Base) -> Option<i32> {
+    i32::from_str_radix(&literal_digits(text, base), base.radix()).ok()
+}
+
+/// Reads an integer literal too large for an `Int32` as an `Int128`, or as a `BigInt` with
+/// the `big-int` feature when it doesn't fit in that either.
+pub fn parse_wide_integer(text: &str, base: Base) -> Option<HugValue> {
+    if let Ok(v) = i128::from_str_radix(&literal_digits(text, base), base.radix()) {
+        return Some(HugValue::from(v));
+    }
+
+    #[cfg(feature = "big-int")]
+    return HugValue::parse_from_type(TypeKind::BigInt, text.to_string(), 0..text.len()).ok();
+    #[cfg(not(feature = "big-int"))]
+    None
+}
+
+/// Converts a byte offset into `program` to a line and column, both starting at 1. Columns
+/// count chars, not bytes. Lines can end in `\n`, `\r\n` or a lone `\r`.
+pub fn line_column(program: &str, offset: usize) -> (usize, usize) {
+    let before = &program[..offset.min(program.len())];
+
+    let mut line = 1;
+    let mut line_start = 0;
+    for (i, c) in before.char_indices() {
+        // The `\r` of a `\r\n` is part of the line, the `\n` ends it
+        if c == '\n' || (c == '\r' && !program[i + 1..].starts_with('\n')) {
+            line += 1;
+            line_start = i + 1;
+        }
+    }
+
+    (line, before[line_start..].chars().count() + 1)
+}
+
 pub fn generate_pairs(program: &str, tokens: Vec<Token>) -> Vec<TokenPair> {
     let mut pairs = Vec::new();
 
-    let mut chars = program.chars();
+    // Token lengths are in bytes, not chars
+    let mut offset = 0;
     for token in tokens {
-        let mut buffer = String::new();
-        for _i in 0..token.len {
-            buffer.push(chars.next().unwrap());
-        }
-
-        pairs.push(TokenPair {
-            text: buffer,
-            token,
-        })
+        let span = offset..offset + token.len;
+        let text = program[span.clone()].to_string();
+        pairs.push(Spanned::new(Lexeme { text, token }, span));
+        offset += token.len;
     }
 
     pairs
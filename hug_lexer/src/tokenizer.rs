@@ -4,7 +4,7 @@ use hug_lib::Ident;
 
 type TokenList = Vec<Token>;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Token {
     pub kind: TokenKind,
     pub len: usize,
@@ -26,6 +26,8 @@ pub enum TokenKind {
     // Not specific to any usage
     Comma,            //  ,
     Dot,              //  .
+    Ellipsis,         //  ...
+    OptionalDot,      //  ?.
     OpenParenthesis,  //  (
     CloseParenthesis, //  )
     OpenBrace,        //  {
@@ -33,6 +35,7 @@ pub enum TokenKind {
     OpenBracket,      //  [
     CloseBracket,     //  ]
     Colon,            //  :
+    Arrow,            //  ->
 
     // Operators
     Assign,         //  =
@@ -59,38 +62,57 @@ pub enum TokenKind {
     GreaterThanOrEquals, //  >=
 
     // Binary operators
-    BinaryAnd,          //  &
-    BinaryOr,           //  |
-    BinaryNot,          //  ~
-    BinaryXOr,          //  ^
-    BinaryAndAssign,    //  &=
-    BinaryOrAssign,     //  |=
-    BinaryNotAssign,    //  ~=
-    BinaryXOrAssign,    //  ^=
-    ShiftLeft,          //  <<
-    ShiftRight,         //  >>
-    ShiftLeftOverflow,  //  <<<
-    ShiftRightOverflow, //  >>>
+    BinaryAnd,                //  &
+    BinaryOr,                 //  |
+    BinaryNot,                //  ~
+    BinaryXOr,                //  ^
+    BinaryAndAssign,          //  &=
+    BinaryOrAssign,           //  |=
+    BinaryNotAssign,          //  ~=
+    BinaryXOrAssign,          //  ^=
+    ShiftLeft,                //  <<
+    ShiftRight,               //  >>
+    ShiftLeftOverflow,        //  <<<
+    ShiftRightOverflow,       //  >>>
+    ShiftLeftAssign,          //  <<=
+    ShiftRightAssign,         //  >>=
+    ShiftLeftOverflowAssign,  //  <<<=
+    ShiftRightOverflowAssign, //  >>>=
 
     Unknown, // Error
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AnnotationKind {
+    Deprecated,
     Extern,
     Other(Ident),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum KeywordKind {
+    Else,
     Enum,
     Function,
+    If,
+    In,
     Let,
     Module,
     Private,
     Public,
+    Return,
     Type,
+    TypeOf,
     Use,
+    While,
+}
+
+impl KeywordKind {
+    /// Checks if a statement can begin with this keyword, `else` and `in` can only
+    /// continue one.
+    pub fn starts_statement(self) -> bool {
+        !matches!(self, KeywordKind::Else | KeywordKind::In)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -112,6 +134,21 @@ pub enum Base {
     Decimal,
 }
 
+impl Base {
+    pub fn radix(self) -> u32 {
+        match self {
+            Base::Binary => 2,
+            Base::Octal => 8,
+            Base::Hexadecimal => 16,
+            Base::Decimal => 10,
+        }
+    }
+
+    pub fn is_digit(self, c: char) -> bool {
+        c.is_digit(self.radix())
+    }
+}
+
 impl TokenKind {
     pub fn expect_literal(self) -> Option<LiteralKind> {
         if let Self::Literal(k) = self {
@@ -144,6 +181,96 @@ impl TokenKind {
             None
         }
     }
+
+    /// Checks if this is an operator that goes between two operands, like `+`, `<=` or `in`.
+    pub fn is_binary_operator(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Add
+                | TokenKind::Subtract
+                | TokenKind::Multiply
+                | TokenKind::Divide
+                | TokenKind::Modulus
+                | TokenKind::And
+                | TokenKind::Or
+                | TokenKind::IsEqualTo
+                | TokenKind::IsNotEqualTo
+                | TokenKind::LessThan
+                | TokenKind::GreaterThan
+                | TokenKind::LessThanOrEquals
+                | TokenKind::GreaterThanOrEquals
+                | TokenKind::BinaryAnd
+                | TokenKind::BinaryOr
+                | TokenKind::BinaryXOr
+                | TokenKind::ShiftLeft
+                | TokenKind::ShiftRight
+                | TokenKind::ShiftLeftOverflow
+                | TokenKind::ShiftRightOverflow
+                | TokenKind::Keyword(KeywordKind::In)
+        )
+    }
+
+    /// Checks if this is `=` or one of the compound assignments like `+=`.
+    pub fn is_assignment_operator(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Assign
+                | TokenKind::AddAssign
+                | TokenKind::SubtractAssign
+                | TokenKind::MultiplyAssign
+                | TokenKind::DivideAssign
+                | TokenKind::ModulusAssign
+                | TokenKind::BinaryAndAssign
+                | TokenKind::BinaryOrAssign
+                | TokenKind::BinaryNotAssign
+                | TokenKind::BinaryXOrAssign
+                | TokenKind::ShiftLeftAssign
+                | TokenKind::ShiftRightAssign
+                | TokenKind::ShiftLeftOverflowAssign
+                | TokenKind::ShiftRightOverflowAssign
+        )
+    }
+
+    /// Checks if this is a bracket or a separator.
+    pub fn is_delimiter(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Comma
+                | TokenKind::Colon
+                | TokenKind::OpenParenthesis
+                | TokenKind::CloseParenthesis
+                | TokenKind::OpenBrace
+                | TokenKind::CloseBrace
+                | TokenKind::OpenBracket
+                | TokenKind::CloseBracket
+        )
+    }
+}
+
+/// Checks if `c` can be the first character of an identifier. With the `unicode-idents`
+/// feature this follows UAX #31 (`XID_Start`), otherwise only ASCII letters are allowed.
+pub fn is_ident_start(c: char) -> bool {
+    #[cfg(feature = "unicode-idents")]
+    let is_start = unic_ucd_ident::is_xid_start(c);
+    #[cfg(not(feature = "unicode-idents"))]
+    let is_start = c.is_ascii_alphabetic();
+
+    is_start || c == '_'
+}
+
+/// Checks if `c` can appear after the first character of an identifier, see [`is_ident_start`].
+pub fn is_ident_continue(c: char) -> bool {
+    #[cfg(feature = "unicode-idents")]
+    let is_continue = unic_ucd_ident::is_xid_continue(c);
+    #[cfg(not(feature = "unicode-idents"))]
+    let is_continue = c.is_ascii_alphanumeric();
+
+    is_continue || c == '_'
+}
+
+pub fn is_valid_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_ident_start) && chars.all(is_ident_continue)
 }
 
 pub struct Tokenizer<'a> {
@@ -177,6 +304,7 @@ impl<'a> Tokenizer<'a> {
         self.len = self.chars.as_str().len();
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<char> {
         self.chars.next()
     }
@@ -201,10 +329,14 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    /// Reads a line comment up to and including its line ending, which is `\n`, `\r\n` or a
+    /// lone `\r`.
     pub fn line_comment(&mut self) -> TokenKind {
         self.next().unwrap(); // Skip /[/]
-        self.ignore_until(|c| c == '\n');
-        self.next();
+        self.ignore_until(|c| c == '\n' || c == '\r');
+        if self.next() == Some('\r') && self.peek_next() == '\n' {
+            self.next();
+        }
         TokenKind::LineComment
     }
 
@@ -235,6 +367,10 @@ impl<'a> Tokenizer<'a> {
                 TokenKind::BinaryXOr => TokenKind::BinaryXOrAssign,
                 TokenKind::BinaryAnd => TokenKind::BinaryAndAssign,
                 TokenKind::BinaryOr => TokenKind::BinaryOrAssign,
+                TokenKind::ShiftLeft => TokenKind::ShiftLeftAssign,
+                TokenKind::ShiftRight => TokenKind::ShiftRightAssign,
+                TokenKind::ShiftLeftOverflow => TokenKind::ShiftLeftOverflowAssign,
+                TokenKind::ShiftRightOverflow => TokenKind::ShiftRightOverflowAssign,
                 other => panic!("Unrecognized operator: {:?}", other),
             }
         } else {
@@ -285,15 +421,28 @@ impl<'a> Tokenizer<'a> {
             Base::Decimal
         };
 
+        if base != Base::Decimal {
+            self.next(); // Skip 0[x]
+        }
+
         while !self.is_eof() {
             let c = self.peek_next();
-            if c == '.' || c == 'f' {
+            if c == '.' || (c == 'f' && base == Base::Decimal) {
                 if kind.is_none() {
                     kind = Some(LiteralKind::Float(base));
                 } else {
                     break;
                 }
-            } else if !c.is_numeric() && c != '_' {
+            } else if (c == 'p' || c == 'P') && base == Base::Hexadecimal {
+                // Binary exponent of a hexadecimal float, 0x1.8[p+1]
+                kind = Some(LiteralKind::Float(base));
+                self.next();
+                if matches!(self.peek_next(), '+' | '-') {
+                    self.next();
+                }
+                self.ignore_until(|c| !c.is_ascii_digit());
+                break;
+            } else if !base.is_digit(c) && c != '_' {
                 break;
             }
 
@@ -305,25 +454,21 @@ impl<'a> Tokenizer<'a> {
 
     pub fn annotation(&mut self) -> TokenKind {
         let mut buffer = String::new();
-        while self.peek_next().is_alphanumeric() && !self.is_eof() {
+        while is_ident_continue(self.peek_next()) && !self.is_eof() {
             let c = self.next().unwrap();
             buffer.push(c);
         }
 
         let kind = match buffer.as_ref() {
+            "deprecated" => AnnotationKind::Deprecated,
             "extern" => AnnotationKind::Extern,
             other => {
-                if other.len() == 0 {
+                if other.is_empty() {
                     return TokenKind::Unknown;
                 }
 
-                for (i, ch) in other.chars().enumerate() {
-                    // If not a valid identifier name
-                    if !((ch.is_alphabetic() && i == 0) || (ch.is_alphanumeric() && i != 0))
-                        && ch != '_'
-                    {
-                        return TokenKind::Unknown;
-                    }
+                if !is_valid_ident(other) {
+                    return TokenKind::Unknown;
                 }
 
                 if let Some(id) = self.idents.get(other) {
@@ -360,21 +505,13 @@ impl<'a> Tokenizer<'a> {
             TokenKind::Assign if next_char == '=' => TokenKind::IsEqualTo,
             TokenKind::LessThan if next_char == '=' => TokenKind::LessThanOrEquals,
             TokenKind::LessThan if next_char == '<' => {
-                if self.peek_next_next() == '<' {
-                    self.next();
-                    TokenKind::ShiftLeftOverflow
-                } else {
-                    TokenKind::ShiftLeft
-                }
+                self.next();
+                return self.shift(TokenKind::ShiftLeft);
             }
             TokenKind::GreaterThan if next_char == '=' => TokenKind::GreaterThanOrEquals,
             TokenKind::GreaterThan if next_char == '>' => {
-                if self.peek_next_next() == '>' {
-                    self.next();
-                    TokenKind::ShiftRightOverflow
-                } else {
-                    TokenKind::ShiftRight
-                }
+                self.next();
+                return self.shift(TokenKind::ShiftRight);
             }
             _ => kind,
         };
@@ -386,41 +523,59 @@ impl<'a> Tokenizer<'a> {
         new_kind
     }
 
+    /// Continues a shift after its first two characters, always taking the longest
+    /// operator, so `<<<=` is one token instead of `<<<` followed by `=`.
+    pub fn shift(&mut self, shift: TokenKind) -> TokenKind {
+        let (ch, overflow) = match shift {
+            TokenKind::ShiftLeft => ('<', TokenKind::ShiftLeftOverflow),
+            TokenKind::ShiftRight => ('>', TokenKind::ShiftRightOverflow),
+            other => panic!("Unrecognized shift: {:?}", other),
+        };
+
+        if self.peek_next() == ch {
+            self.next();
+            self.operator(overflow)
+        } else {
+            self.operator(shift)
+        }
+    }
+
     pub fn try_keyword(&mut self, first_char: char) -> TokenKind {
         let mut buffer = String::new();
         buffer.push(first_char);
 
         while {
             let c = self.peek_next();
-            c.is_alphanumeric() || c == '_'
+            is_ident_continue(c)
         } && !self.is_eof()
         {
             buffer.push(self.next().unwrap());
         }
 
         match buffer.as_str() {
+            "else" => TokenKind::Keyword(KeywordKind::Else),
             "enum" => TokenKind::Keyword(KeywordKind::Enum),
             "function" => TokenKind::Keyword(KeywordKind::Function),
+            "if" => TokenKind::Keyword(KeywordKind::If),
+            "in" => TokenKind::Keyword(KeywordKind::In),
             "let" => TokenKind::Keyword(KeywordKind::Let),
             "module" => TokenKind::Keyword(KeywordKind::Module),
             "private" => TokenKind::Keyword(KeywordKind::Private),
             "public" => TokenKind::Keyword(KeywordKind::Public),
+            "return" => TokenKind::Keyword(KeywordKind::Return),
             "type" => TokenKind::Keyword(KeywordKind::Type),
+            "typeof" => TokenKind::Keyword(KeywordKind::TypeOf),
             "use" => TokenKind::Keyword(KeywordKind::Use),
+            "while" => TokenKind::Keyword(KeywordKind::While),
             "true" => TokenKind::Literal(LiteralKind::Boolean),
             "false" => TokenKind::Literal(LiteralKind::Boolean),
             other => {
-                if other.len() == 0 {
+                if other.is_empty() {
                     return TokenKind::Unknown;
                 }
 
-                for (i, ch) in other.chars().enumerate() {
-                    // If not a valid identifier name
-                    if !((ch.is_alphabetic() && i == 0) || (ch.is_alphanumeric() && i != 0))
-                        && ch != '_'
-                    {
-                        return TokenKind::Unknown;
-                    }
+                if !is_valid_ident(other) {
+                    return TokenKind::Unknown;
                 }
 
                 if let Some(id) = self.idents.get(other) {
@@ -463,7 +618,16 @@ impl<'a> Tokenizer<'a> {
 
             // Others
             ',' => TokenKind::Comma,
+            '.' if self.peek_next() == '.' && self.peek_next_next() == '.' => {
+                self.next();
+                self.next();
+                TokenKind::Ellipsis
+            }
             '.' => TokenKind::Dot,
+            '?' if self.peek_next() == '.' => {
+                self.next();
+                TokenKind::OptionalDot
+            }
             '(' => TokenKind::OpenParenthesis,
             ')' => TokenKind::CloseParenthesis,
             '{' => TokenKind::OpenBrace,
@@ -475,7 +639,11 @@ impl<'a> Tokenizer<'a> {
             // Common operators
             // +, +=
             '+' => self.operator(TokenKind::Add),
-            // -, -=
+            // -, -=, ->
+            '-' if self.peek_next() == '>' => {
+                self.next();
+                TokenKind::Arrow
+            }
             '-' => self.operator(TokenKind::Subtract),
             // *, *=
             '*' => self.operator(TokenKind::Multiply),
@@ -498,9 +666,9 @@ impl<'a> Tokenizer<'a> {
             '&' => self.condition(TokenKind::BinaryAnd),
             // |, ||
             '|' => self.condition(TokenKind::BinaryOr),
-            // <, <<, <<<, <=
+            // <, <=, <<, <<=, <<<, <<<=
             '<' => self.condition(TokenKind::LessThan),
-            // >, >>, >>>, >=
+            // >, >=, >>, >>=, >>>, >>>=
             '>' => self.condition(TokenKind::GreaterThan),
 
             emoji if !emoji.is_ascii() && unic_emoji_char::is_emoji(emoji) => {
@@ -1,8 +1,14 @@
 use hug_lexer::{
-    run_test,
+    lex,
+    parser::line_column,
+    run_test, tokenize_operators,
     tokenizer::{Base, KeywordKind, LiteralKind, TokenKind},
+    FilterUseless,
+};
+use hug_lib::{
+    value::{parse_hex_float, HugValue, TypeKind},
+    Ident,
 };
-use hug_lib::Ident;
 
 const COMMENTS_PROGRAM: &str = r###"
 // Hello
@@ -121,3 +127,238 @@ const VARIABLES_EXPECTED_RESULT: &[(TokenKind, usize)] = &[
 fn variables() {
     run_test(VARIABLES_PROGRAM, VARIABLES_EXPECTED_RESULT);
 }
+
+#[cfg(feature = "unicode-idents")]
+#[test]
+fn unicode_identifiers() {
+    run_test(
+        "let café = 1",
+        &[
+            (TokenKind::Keyword(KeywordKind::Let), 3),
+            (TokenKind::Whitespace, 1),
+            (TokenKind::Identifier(Ident(0)), 5), // Lengths are in bytes
+            (TokenKind::Whitespace, 1),
+            (TokenKind::Assign, 1),
+        ],
+    );
+}
+
+#[cfg(not(feature = "unicode-idents"))]
+#[test]
+fn ascii_identifiers() {
+    run_test(
+        "café",
+        &[
+            (TokenKind::Identifier(Ident(0)), 3),
+            (TokenKind::Unknown, 2),
+        ],
+    );
+}
+
+#[test]
+fn hexadecimal_floats() {
+    run_test(
+        "0x1.8p1 0xFF 0x1.921fb54442d18p+1",
+        &[
+            (TokenKind::Literal(LiteralKind::Float(Base::Hexadecimal)), 7),
+            (TokenKind::Whitespace, 1),
+            (
+                TokenKind::Literal(LiteralKind::Integer(Base::Hexadecimal)),
+                4,
+            ),
+            (TokenKind::Whitespace, 1),
+            (
+                TokenKind::Literal(LiteralKind::Float(Base::Hexadecimal)),
+                20,
+            ),
+        ],
+    );
+
+    assert_eq!(parse_hex_float("0x1.8p1"), Some(3.0));
+    assert_eq!(
+        parse_hex_float("0x1.921fb54442d18p+1"),
+        Some(std::f64::consts::PI)
+    );
+    assert_eq!(parse_hex_float("0x.8"), Some(0.5));
+    assert_eq!(parse_hex_float("0x1p-2"), Some(0.25));
+    assert_eq!(parse_hex_float("0xp1"), None);
+}
+
+#[test]
+fn typeof_keyword() {
+    run_test(
+        "typeof type_",
+        &[
+            (TokenKind::Keyword(KeywordKind::TypeOf), 6),
+            (TokenKind::Whitespace, 1),
+            (TokenKind::Identifier(Ident(0)), 5),
+        ],
+    );
+}
+
+#[test]
+fn in_keyword() {
+    run_test(
+        "key in map",
+        &[
+            (TokenKind::Identifier(Ident(0)), 3),
+            (TokenKind::Whitespace, 1),
+            (TokenKind::Keyword(KeywordKind::In), 2),
+            (TokenKind::Whitespace, 1),
+            (TokenKind::Identifier(Ident(1)), 3),
+        ],
+    );
+}
+
+#[test]
+fn operator_maximal_munch() {
+    use TokenKind::*;
+
+    let a = Identifier(Ident(0));
+    let b = Identifier(Ident(1));
+
+    assert_eq!(tokenize_operators("a < <b"), [a, LessThan, LessThan, b]);
+    assert_eq!(tokenize_operators("a << b"), [a, ShiftLeft, b]);
+    assert_eq!(tokenize_operators("a <= b"), [a, LessThanOrEquals, b]);
+    assert_eq!(tokenize_operators("a<=b"), [a, LessThanOrEquals, b]);
+    assert_eq!(tokenize_operators("a <<= b"), [a, ShiftLeftAssign, b]);
+    assert_eq!(tokenize_operators("a<<<b"), [a, ShiftLeftOverflow, b]);
+    assert_eq!(
+        tokenize_operators("a <<<= b"),
+        [a, ShiftLeftOverflowAssign, b]
+    );
+    assert_eq!(tokenize_operators("a < = b"), [a, LessThan, Assign, b]);
+    assert_eq!(
+        tokenize_operators("a<<<<b"),
+        [a, ShiftLeftOverflow, LessThan, b]
+    );
+    assert_eq!(tokenize_operators("a >>= b"), [a, ShiftRightAssign, b]);
+    assert_eq!(tokenize_operators("a>>>b"), [a, ShiftRightOverflow, b]);
+    assert_eq!(tokenize_operators("a...b"), [a, Ellipsis, b]);
+    assert_eq!(tokenize_operators("a..b"), [a, Dot, Dot, b]);
+    assert_eq!(tokenize_operators("a?.b"), [a, OptionalDot, b]);
+    assert_eq!(tokenize_operators("a->b"), [a, Arrow, b]);
+    assert_eq!(tokenize_operators("a-=b"), [a, SubtractAssign, b]);
+}
+
+#[test]
+fn token_kind_groups() {
+    use TokenKind::*;
+
+    assert!(Add.is_binary_operator());
+    assert!(!Add.is_assignment_operator());
+    assert!(!Add.is_delimiter());
+
+    assert!(AddAssign.is_assignment_operator());
+    assert!(!AddAssign.is_binary_operator());
+    assert!(!AddAssign.is_delimiter());
+
+    assert!(OpenBrace.is_delimiter());
+    assert!(!OpenBrace.is_binary_operator());
+    assert!(!OpenBrace.is_assignment_operator());
+
+    assert!(Keyword(KeywordKind::In).is_binary_operator());
+    assert!(!Not.is_binary_operator());
+}
+
+#[test]
+fn type_names() {
+    let types: Vec<_> = lex("Point Vector Point Int32")
+        .filter_useless()
+        .iter()
+        .map(|pair| pair.expect_type().unwrap())
+        .collect();
+
+    assert_eq!(types[0], TypeKind::Other(Ident(0)));
+    assert_eq!(types[0], types[2]);
+    assert_ne!(types[0], types[1]);
+    assert_eq!(types[3], TypeKind::Int32);
+}
+
+#[test]
+fn wide_integers() {
+    let literal = |program| lex(program)[0].parse_literal();
+
+    assert!(matches!(
+        literal("2147483647"),
+        Some(HugValue::Int32(i32::MAX))
+    ));
+    assert!(matches!(
+        literal("2147483648"),
+        Some(HugValue::Int128(2147483648))
+    ));
+    assert!(matches!(
+        literal("0xFFFF_FFFF"),
+        Some(HugValue::Int128(0xFFFF_FFFF))
+    ));
+}
+
+#[cfg(feature = "big-int")]
+#[test]
+fn big_integers() {
+    let literal = |program| lex(program)[0].parse_literal();
+
+    assert!(matches!(
+        literal("170141183460469231731687303715884105728"),
+        Some(HugValue::BigInt(v)) if v.to_string() == "170141183460469231731687303715884105728"
+    ));
+    assert!(matches!(
+        literal("0x1_0000_0000_0000_0000_0000_0000_0000_0000"),
+        Some(HugValue::BigInt(v)) if v.to_string() == "340282366920938463463374607431768211456"
+    ));
+}
+
+#[test]
+fn token_positions() {
+    let program = "let a = \"ü\"\n  let b = 2";
+    let pairs = lex(program).filter_useless();
+
+    assert_eq!(pairs[4].text, "let");
+    assert_eq!(line_column(program, pairs[4].span.start), (2, 3));
+    assert_eq!(line_column(program, pairs[3].span.end), (1, 12));
+    assert_eq!(line_column(program, 0), (1, 1));
+    assert_eq!(line_column(program, program.len()), (2, 12));
+}
+
+#[test]
+fn literal_text_round_trip() {
+    let program = "let a = 0xFF_00 let b = 1_000.5 // c\nlet d = 0b1010";
+    let pairs = lex(program);
+
+    let literals: Vec<&str> = pairs
+        .iter()
+        .filter(|pair| matches!(pair.token.kind, TokenKind::Literal(_)))
+        .map(|pair| pair.text.as_str())
+        .collect();
+    assert_eq!(literals, ["0xFF_00", "1_000.5", "0b1010"]);
+
+    let source: String = pairs.iter().map(|pair| pair.text.as_str()).collect();
+    assert_eq!(source, program);
+}
+
+#[test]
+fn line_endings() {
+    let program = "let a = 1 // one\r\nlet b = 2\r\n\r\n  let c = 3 // three\rlet d = 4\r";
+    let pairs = lex(program);
+
+    assert!(pairs
+        .iter()
+        .all(|pair| pair.token.kind != TokenKind::Unknown));
+
+    let comments: Vec<&str> = pairs
+        .iter()
+        .filter(|pair| pair.token.kind == TokenKind::LineComment)
+        .map(|pair| pair.text.as_str())
+        .collect();
+    assert_eq!(comments, ["// one\r\n", "// three\r"]);
+
+    let lets: Vec<(usize, usize)> = pairs
+        .iter()
+        .filter(|pair| pair.token.kind == TokenKind::Keyword(KeywordKind::Let))
+        .map(|pair| line_column(program, pair.span.start))
+        .collect();
+    assert_eq!(lets, [(1, 1), (2, 1), (4, 3), (5, 1)]);
+
+    assert_eq!(line_column(program, program.len()), (6, 1));
+    assert_eq!(line_column("a\r\nb", 2), (1, 3));
+}
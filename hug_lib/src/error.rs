@@ -0,0 +1,199 @@
+use std::{error::Error, fmt::Display, ops::Range};
+
+use crate::{
+    value::{HugValue, TypeKind},
+    Ident,
+};
+
+/// A value had a different type than was needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    /// The name of the type that was needed, like `"String"`, or a description of the types
+    /// that would have worked, like `"a number"`.
+    pub expected: &'static str,
+    /// The type of the value that was given instead.
+    pub found: TypeKind,
+}
+
+impl TypeError {
+    pub fn new(expected: &'static str, found: &HugValue) -> TypeError {
+        TypeError {
+            expected,
+            found: found.kind(),
+        }
+    }
+
+    /// The error for an operator that can't be used on `lhs` and `rhs`. Operands of different
+    /// types expect `rhs` to be the type of `lhs`, operands of the same type expect a number.
+    pub fn operands(lhs: &HugValue, rhs: &HugValue) -> TypeError {
+        if lhs.kind() == rhs.kind() {
+            TypeError::new("a number", lhs)
+        } else {
+            TypeError::new(lhs.kind().name(), rhs)
+        }
+    }
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found.name())
+    }
+}
+
+impl Error for TypeError {}
+
+/// Errors that can occur when calling a function value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError {
+    /// The function was called with a different number of arguments than it takes.
+    ArityMismatch { expected: usize, found: usize },
+    /// The called value is not a function.
+    NotCallable,
+    /// A function defined in a script was used where only an external function can be called.
+    NotExternal,
+    /// An argument could not be converted to the type the function takes.
+    TypeError(TypeError),
+    /// An argument was passed by a name the function has no argument for.
+    UnknownArgument(Ident),
+    /// An argument was passed more than once, by position or by name.
+    DuplicateArgument(Ident),
+    /// An argument without a default value wasn't passed.
+    MissingArgument(Ident),
+}
+
+impl Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallError::ArityMismatch { expected, found } => write!(
+                f,
+                "expected {} argument(s), but {} were given",
+                expected, found
+            ),
+            CallError::NotCallable => write!(f, "value is not a function"),
+            CallError::NotExternal => {
+                write!(
+                    f,
+                    "a script function can't be called as an external function"
+                )
+            }
+            CallError::TypeError(e) => write!(f, "invalid argument: {}", e),
+            CallError::UnknownArgument(id) => write!(f, "there is no argument {:?}", id),
+            CallError::DuplicateArgument(id) => {
+                write!(f, "argument {:?} was passed more than once", id)
+            }
+            CallError::MissingArgument(id) => write!(f, "argument {:?} was not passed", id),
+        }
+    }
+}
+
+impl Error for CallError {}
+
+impl From<TypeError> for CallError {
+    fn from(e: TypeError) -> CallError {
+        CallError::TypeError(e)
+    }
+}
+
+/// Errors that can occur when evaluating an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// The expression can only be known by running the program, like a function call.
+    NonConstant,
+    /// An integer was divided by zero.
+    DivideByZero,
+    TypeError(TypeError),
+    /// A variable was used that has no value.
+    UndefinedVariable(Ident),
+    /// An array or string was indexed past its end, or with a negative index.
+    IndexOutOfBounds {
+        index: String,
+        len: usize,
+    },
+    /// A struct was accessed with a field its type doesn't have.
+    UnknownField(Ident),
+    /// A map was indexed with a key it doesn't have.
+    MissingKey(String),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::NonConstant => write!(f, "expression is not constant"),
+            EvalError::DivideByZero => write!(f, "attempted to divide by zero"),
+            EvalError::TypeError(e) => write!(f, "{}", e),
+            EvalError::UndefinedVariable(id) => write!(f, "variable {:?} is not defined", id),
+            EvalError::IndexOutOfBounds { index, len } => {
+                write!(f, "index {} is out of bounds for length {}", index, len)
+            }
+            EvalError::UnknownField(id) => write!(f, "there is no field {:?}", id),
+            EvalError::MissingKey(key) => write!(f, "there is no key {:?}", key),
+        }
+    }
+}
+
+impl Error for EvalError {}
+
+impl From<TypeError> for EvalError {
+    fn from(e: TypeError) -> EvalError {
+        EvalError::TypeError(e)
+    }
+}
+
+/// Errors that can occur when creating a value from the text of a literal, see
+/// [`TypedDefinition`](crate::value::TypedDefinition).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseValueError {
+    /// The number is too big or too small for the type.
+    IntegerOverflow {
+        _type: TypeKind,
+        value: String,
+        span: Range<usize>,
+    },
+    /// The text isn't a literal of the type.
+    Invalid {
+        _type: TypeKind,
+        value: String,
+        span: Range<usize>,
+    },
+    /// The type is user-defined, which can't be written as a literal.
+    NoLiteral {
+        _type: TypeKind,
+        value: String,
+        span: Range<usize>,
+    },
+}
+
+impl ParseValueError {
+    /// Where the literal is in the program, in bytes.
+    pub fn span(&self) -> &Range<usize> {
+        match self {
+            ParseValueError::IntegerOverflow { span, .. }
+            | ParseValueError::Invalid { span, .. }
+            | ParseValueError::NoLiteral { span, .. } => span,
+        }
+    }
+}
+
+impl Display for ParseValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseValueError::IntegerOverflow { _type, value, span } => write!(
+                f,
+                "{} doesn't fit in {:?} (at {}..{})",
+                value, _type, span.start, span.end
+            ),
+            ParseValueError::Invalid { _type, value, span } => write!(
+                f,
+                "{} is not a valid {:?} (at {}..{})",
+                value, _type, span.start, span.end
+            ),
+            ParseValueError::NoLiteral { _type, value, span } => write!(
+                f,
+                "{} can't be a {:?}, it has no literals (at {}..{})",
+                value, _type, span.start, span.end
+            ),
+        }
+    }
+}
+
+impl Error for ParseValueError {}
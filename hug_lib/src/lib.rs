@@ -1,6 +1,9 @@
 use std::collections::HashMap;
-use value::HugValue;
+use value::HugExternalFunction;
 
+pub mod error;
+pub mod prelude;
+pub mod span;
 pub mod value;
 
 #[macro_export]
@@ -33,23 +36,19 @@ macro_rules! unwrap_args {
 pub struct Ident(pub usize);
 
 pub struct HugModule<'a> {
-    pub functions: HashMap<Ident, fn(std::vec::IntoIter<HugValue>) -> Option<HugValue>>,
+    pub functions: HashMap<Ident, HugExternalFunction>,
     idents: &'a mut HashMap<String, Ident>,
 }
 
 impl<'a> HugModule<'a> {
-    pub fn new(idents: &mut HashMap<String, Ident>) -> HugModule {
+    pub fn new(idents: &mut HashMap<String, Ident>) -> HugModule<'_> {
         HugModule {
             functions: HashMap::new(),
             idents,
         }
     }
 
-    pub fn register_function(
-        &mut self,
-        name: &str,
-        func: fn(std::vec::IntoIter<HugValue>) -> Option<HugValue>,
-    ) {
+    pub fn register_function(&mut self, name: &str, func: HugExternalFunction) {
         if let Some(id) = self.idents.get(name) {
             self.functions.insert(*id, func);
         } else {
@@ -0,0 +1,9 @@
+//! The types and traits needed by most code that works with hug values, import them all with
+//! `use hug_lib::prelude::*`.
+
+pub use crate::{
+    error::{CallError, EvalError, TypeError},
+    span::Spanned,
+    value::{FromHugValue, HugValue},
+    HugModule, Ident,
+};
@@ -0,0 +1,45 @@
+use std::ops::{Deref, DerefMut, Range};
+
+/// A value together with where it is in the program, in bytes. It derefs to the value, so
+/// `spanned.len()` works just like it would on the value itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Range<usize>,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Range<usize>) -> Spanned<T> {
+        Spanned { node, span }
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.node
+    }
+
+    /// Replaces the value with the result of `f`, keeping the span.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned {
+            node: f(self.node),
+            span: self.span,
+        }
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.node
+    }
+}
@@ -1,13 +1,46 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    convert::Infallible,
+    fmt::Display,
+    num::IntErrorKind,
+    ops::{Add, BitAnd, BitOr, BitXor, Div, Index, Mul, Neg, Not, Range, Rem, Shl, Shr, Sub},
+    str::FromStr,
+};
+
+#[cfg(feature = "big-int")]
+use num_bigint::{BigInt, Sign};
+#[cfg(feature = "big-int")]
+use num_traits::ToPrimitive;
+
+use crate::{
+    error::{CallError, EvalError, ParseValueError, TypeError},
+    Ident,
+};
+
 pub type HugExternalFunction = fn(std::vec::IntoIter<HugValue>) -> Option<HugValue>;
 
+/// Orders two values for [`HugValue::array_sort`], like [`HugValue::total_cmp`].
+pub type CompareOp = fn(&HugValue, &HugValue) -> Result<Ordering, TypeError>;
+
+/// Where a call to a function value has to go, see [`HugValue::callee`].
+#[derive(Debug, Clone, Copy)]
+pub enum Callee {
+    /// A function defined in a script, which only the interpreter can run, starting at this
+    /// instruction.
+    Interpreted(usize),
+    /// A function provided by a module, which can be called directly.
+    External(HugExternalFunction),
+}
+
 macro_rules! gen_impls_for_HugValue {
     ($hug_name:ident, $rust_type:ty) => {
         impl FromHugValue for $rust_type {
-            fn from_hug_value(value: HugValue) -> Option<$rust_type> {
+            fn from_hug_value(value: HugValue) -> Result<$rust_type, TypeError> {
                 if let HugValue::$hug_name(v) = value {
-                    Some(v)
+                    Ok(v)
                 } else {
-                    None
+                    Err(TypeError::new(stringify!($hug_name), &value))
                 }
             }
         }
@@ -21,8 +54,9 @@ macro_rules! gen_impls_for_HugValue {
 }
 
 pub trait FromHugValue: Sized {
-    fn from_hug_value(value: HugValue) -> Option<Self>;
+    fn from_hug_value(value: HugValue) -> Result<Self, TypeError>;
 }
+gen_impls_for_HugValue!(Bool, bool);
 gen_impls_for_HugValue!(Int8, i8);
 gen_impls_for_HugValue!(Int16, i16);
 gen_impls_for_HugValue!(Int32, i32);
@@ -33,14 +67,277 @@ gen_impls_for_HugValue!(UInt16, u16);
 gen_impls_for_HugValue!(UInt32, u32);
 gen_impls_for_HugValue!(UInt64, u64);
 gen_impls_for_HugValue!(UInt128, u128);
+#[cfg(feature = "big-int")]
+gen_impls_for_HugValue!(BigInt, BigInt);
 gen_impls_for_HugValue!(Float32, f32);
 gen_impls_for_HugValue!(Float64, f64);
 gen_impls_for_HugValue!(String, String);
+gen_impls_for_HugValue!(Array, Vec<HugValue>);
+gen_impls_for_HugValue!(Map, HashMap<String, HugValue>);
 gen_impls_for_HugValue!(Function, usize);
 gen_impls_for_HugValue!(ExternalFunction, HugExternalFunction);
 
+/// What a [`TypeError`] expects when an array has the wrong number of elements for an array
+/// or tuple type.
+const WRONG_LENGTH: &str = "an array of a different length";
+
+/// Converts an array value with exactly `N` elements.
+impl<T: FromHugValue, const N: usize> FromHugValue for [T; N] {
+    fn from_hug_value(value: HugValue) -> Result<[T; N], TypeError> {
+        let array = Vec::<HugValue>::from_hug_value(value)?;
+        if array.len() != N {
+            return Err(TypeError {
+                expected: WRONG_LENGTH,
+                found: TypeKind::Array,
+            });
+        }
+
+        let elements = array
+            .into_iter()
+            .map(T::from_hug_value)
+            .collect::<Result<Vec<T>, TypeError>>()?;
+        Ok(elements
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("the length was checked")))
+    }
+}
+
+macro_rules! impl_from_hug_value_for_tuple {
+    ($len:literal, $($element:ident),+) => {
+        /// Converts an array value with exactly as many elements as the tuple.
+        impl<$($element: FromHugValue),+> FromHugValue for ($($element,)+) {
+            fn from_hug_value(value: HugValue) -> Result<Self, TypeError> {
+                let array = Vec::<HugValue>::from_hug_value(value)?;
+                if array.len() != $len {
+                    return Err(TypeError {
+                        expected: WRONG_LENGTH,
+                        found: TypeKind::Array,
+                    });
+                }
+
+                let mut array = array.into_iter();
+                Ok(($($element::from_hug_value(array.next().unwrap())?,)+))
+            }
+        }
+    };
+}
+
+impl_from_hug_value_for_tuple!(2, A, B);
+impl_from_hug_value_for_tuple!(3, A, B, C);
+
+/// The type of a [`HugValue`], or a type defined by a script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    Unit,
+    Bool,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Int128,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    UInt128,
+    #[cfg(feature = "big-int")]
+    BigInt,
+    Float32,
+    Float64,
+    String,
+    Array,
+    Map,
+    Function,
+    ExternalFunction,
+    /// A type that isn't built in, identified by the name it was declared with.
+    Other(Ident),
+}
+
+impl TypeKind {
+    /// Looks up a built-in type by the name scripts use for it, like `Int32`.
+    pub fn from_name(name: &str) -> Option<TypeKind> {
+        Some(match name {
+            "Bool" => TypeKind::Bool,
+            "Int8" => TypeKind::Int8,
+            "Int16" => TypeKind::Int16,
+            "Int32" => TypeKind::Int32,
+            "Int64" => TypeKind::Int64,
+            "Int128" => TypeKind::Int128,
+            "UInt8" => TypeKind::UInt8,
+            "UInt16" => TypeKind::UInt16,
+            "UInt32" => TypeKind::UInt32,
+            "UInt64" => TypeKind::UInt64,
+            "UInt128" => TypeKind::UInt128,
+            #[cfg(feature = "big-int")]
+            "BigInt" => TypeKind::BigInt,
+            "Float32" => TypeKind::Float32,
+            "Float64" => TypeKind::Float64,
+            "String" => TypeKind::String,
+            _ => return None,
+        })
+    }
+
+    /// The name of this type as scripts write it, the opposite of [`TypeKind::from_name`].
+    /// Types without a name in scripts get the name of their [`HugValue`] variant.
+    pub fn name(self) -> &'static str {
+        match self {
+            TypeKind::Unit => "Unit",
+            TypeKind::Bool => "Bool",
+            TypeKind::Int8 => "Int8",
+            TypeKind::Int16 => "Int16",
+            TypeKind::Int32 => "Int32",
+            TypeKind::Int64 => "Int64",
+            TypeKind::Int128 => "Int128",
+            TypeKind::UInt8 => "UInt8",
+            TypeKind::UInt16 => "UInt16",
+            TypeKind::UInt32 => "UInt32",
+            TypeKind::UInt64 => "UInt64",
+            TypeKind::UInt128 => "UInt128",
+            #[cfg(feature = "big-int")]
+            TypeKind::BigInt => "BigInt",
+            TypeKind::Float32 => "Float32",
+            TypeKind::Float64 => "Float64",
+            TypeKind::String => "String",
+            TypeKind::Array => "Array",
+            TypeKind::Map => "Map",
+            TypeKind::Function => "Function",
+            TypeKind::ExternalFunction => "ExternalFunction",
+            TypeKind::Other(_) => "a custom type",
+        }
+    }
+}
+
+/// Decodes a hexadecimal float such as `0x1.8p1` (1.5 * 2^1) into the exact `f64` it
+/// describes, the `p` exponent is optional. Rust's `f64::from_str` can't parse these.
+pub fn parse_hex_float(text: &str) -> Option<f64> {
+    let digits = text.strip_prefix("0x")?.replace('_', "");
+    let (mantissa, exponent) = match digits.split_once(['p', 'P']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+        None => (digits.as_str(), 0),
+    };
+    let (int, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if int.is_empty() && fraction.is_empty() {
+        return None;
+    }
+
+    // Every hex digit is exactly 4 bits, so the digits are collected as one big integer
+    // and the fraction is accounted for in the exponent.
+    let mut bits = 0u64;
+    for digit in int.chars().chain(fraction.chars()) {
+        bits = bits
+            .checked_mul(16)?
+            .checked_add(digit.to_digit(16)? as u64)?;
+    }
+    let exponent = exponent.checked_sub(fraction.len() as i32 * 4)?;
+
+    Some(bits as f64 * 2f64.powi(exponent))
+}
+
+/// Values that can be created from the text of a literal once its type is known, like the
+/// `5` in `let x: UInt8 = 5`. `span` is where `value` is in the program, used in errors.
+pub trait TypedDefinition: Sized {
+    fn parse_from_type(
+        _type: TypeKind,
+        value: String,
+        span: Range<usize>,
+    ) -> Result<Self, ParseValueError>;
+}
+
+impl TypedDefinition for HugValue {
+    fn parse_from_type(
+        _type: TypeKind,
+        value: String,
+        span: Range<usize>,
+    ) -> Result<HugValue, ParseValueError> {
+        let invalid = || ParseValueError::Invalid {
+            _type,
+            value: value.clone(),
+            span: span.clone(),
+        };
+
+        let digits = value.replace('_', "");
+        let (radix, digits) = match digits.get(..2) {
+            Some("0x") => (16, &digits[2..]),
+            Some("0o") => (8, &digits[2..]),
+            Some("0b") => (2, &digits[2..]),
+            _ => (10, digits.as_str()),
+        };
+
+        macro_rules! int {
+            ($variant:ident, $rust_type:ty) => {
+                <$rust_type>::from_str_radix(digits, radix)
+                    .map(HugValue::$variant)
+                    .map_err(|e| match e.kind() {
+                        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                            ParseValueError::IntegerOverflow {
+                                _type,
+                                value: value.clone(),
+                                span: span.clone(),
+                            }
+                        }
+                        _ => invalid(),
+                    })
+            };
+        }
+
+        macro_rules! float {
+            ($variant:ident, $rust_type:ty) => {
+                match radix {
+                    10 => digits
+                        .trim_end_matches('f')
+                        .parse::<$rust_type>()
+                        .map(HugValue::$variant)
+                        .map_err(|_| invalid()),
+                    16 => parse_hex_float(&value)
+                        .map(|v| HugValue::$variant(v as $rust_type))
+                        .ok_or_else(invalid),
+                    _ => Err(invalid()),
+                }
+            };
+        }
+
+        match _type {
+            TypeKind::Bool => match value.as_str() {
+                "true" => Ok(HugValue::Bool(true)),
+                "false" => Ok(HugValue::Bool(false)),
+                _ => Err(invalid()),
+            },
+            TypeKind::Int8 => int!(Int8, i8),
+            TypeKind::Int16 => int!(Int16, i16),
+            TypeKind::Int32 => int!(Int32, i32),
+            TypeKind::Int64 => int!(Int64, i64),
+            TypeKind::Int128 => int!(Int128, i128),
+            TypeKind::UInt8 => int!(UInt8, u8),
+            TypeKind::UInt16 => int!(UInt16, u16),
+            TypeKind::UInt32 => int!(UInt32, u32),
+            TypeKind::UInt64 => int!(UInt64, u64),
+            TypeKind::UInt128 => int!(UInt128, u128),
+            #[cfg(feature = "big-int")]
+            TypeKind::BigInt => BigInt::parse_bytes(digits.as_bytes(), radix)
+                .map(HugValue::BigInt)
+                .ok_or_else(invalid),
+            TypeKind::Float32 => float!(Float32, f32),
+            TypeKind::Float64 => float!(Float64, f64),
+            TypeKind::String => value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .map(|v| HugValue::String(v.to_string()))
+                .ok_or_else(invalid),
+            TypeKind::Other(_) => Err(ParseValueError::NoLiteral {
+                _type,
+                value: value.clone(),
+                span: span.clone(),
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum HugValue {
+    /// The absence of a value, like the result of an `if` without `else` whose condition was false.
+    Unit,
+    Bool(bool),
     Int8(i8),
     Int16(i16),
     Int32(i32),
@@ -51,15 +348,954 @@ pub enum HugValue {
     UInt32(u32),
     UInt64(u64),
     UInt128(u128),
+    /// An integer of any size, arithmetic on these never overflows.
+    #[cfg(feature = "big-int")]
+    BigInt(BigInt),
     Float32(f32),
     Float64(f64),
     String(String),
+    Array(Vec<HugValue>),
+    Map(HashMap<String, HugValue>),
+    /// A value of a user-defined type, like `Point { x: 1, y: 2 }`.
+    Struct {
+        type_name: Ident,
+        fields: HashMap<Ident, HugValue>,
+    },
     Function(usize), // usize = pointer to instruction
     ExternalFunction(fn(std::vec::IntoIter<HugValue>) -> Option<HugValue>),
 }
 
+/// Values are equal if they are the same variant holding equal contents, see
+/// [`HugValue::deep_eq`]. Numbers of different types are never equal, so `1 != 1u8`, and NaN
+/// isn't equal to itself. Functions are equal if they point to the same function.
+impl PartialEq for HugValue {
+    fn eq(&self, other: &HugValue) -> bool {
+        self.deep_eq(other)
+    }
+}
+
+impl Display for HugValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HugValue::Unit => write!(f, "none"),
+            HugValue::Bool(v) => write!(f, "{}", v),
+            HugValue::Int8(v) => write!(f, "{}", v),
+            HugValue::Int16(v) => write!(f, "{}", v),
+            HugValue::Int32(v) => write!(f, "{}", v),
+            HugValue::Int64(v) => write!(f, "{}", v),
+            HugValue::Int128(v) => write!(f, "{}", v),
+            HugValue::UInt8(v) => write!(f, "{}", v),
+            HugValue::UInt16(v) => write!(f, "{}", v),
+            HugValue::UInt32(v) => write!(f, "{}", v),
+            HugValue::UInt64(v) => write!(f, "{}", v),
+            HugValue::UInt128(v) => write!(f, "{}", v),
+            #[cfg(feature = "big-int")]
+            HugValue::BigInt(v) => write!(f, "{}", v),
+            HugValue::Float32(v) => write!(f, "{}", v),
+            HugValue::Float64(v) => write!(f, "{}", v),
+            HugValue::String(v) => write!(f, "{}", v),
+            HugValue::Array(array) => {
+                let elements: Vec<String> = array.iter().map(HugValue::to_string).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            HugValue::Map(map) => {
+                // Sorted so the same map is always written the same way
+                let mut entries: Vec<String> = map
+                    .iter()
+                    .map(|(key, value)| format!("{:?}: {}", key, value))
+                    .collect();
+                entries.sort();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            HugValue::Struct { type_name, fields } => {
+                let mut fields: Vec<_> = fields.iter().collect();
+                fields.sort_by_key(|(id, _)| **id);
+                let fields: Vec<String> = fields
+                    .into_iter()
+                    .map(|(id, value)| format!("{:?}: {}", id, value))
+                    .collect();
+                write!(f, "{:?} {{{}}}", type_name, fields.join(", "))
+            }
+            HugValue::Function(target) => write!(f, "function@{}", target),
+            HugValue::ExternalFunction(_) => write!(f, "external function"),
+        }
+    }
+}
+
+/// Reads a value from text without knowing its type, which never fails:
+///
+/// - `true` and `false` become a [`HugValue::Bool`].
+/// - Whole numbers that fit in 32 bits become a [`HugValue::Int32`], leading zeros are
+///   ignored so `"007"` is `7`.
+/// - Other numbers of digits, a sign, `.` and exponents, like `"1.5"`, `"-2e3"` or a whole
+///   number that is too big for an `Int32`, become a [`HugValue::Float64`].
+/// - Anything else becomes a [`HugValue::String`] holding the text as is, including things
+///   like `"0x10"`, `"1_000"`, `"NaN"` and `" 5"` that only look like numbers.
+impl FromStr for HugValue {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<HugValue, Infallible> {
+        let is_decimal = !s.is_empty()
+            && s.bytes()
+                .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b));
+
+        Ok(match s {
+            "true" => HugValue::Bool(true),
+            "false" => HugValue::Bool(false),
+            _ => match (s.parse::<i32>(), s.parse::<f64>()) {
+                (Ok(v), _) => HugValue::Int32(v),
+                (_, Ok(v)) if is_decimal => HugValue::Float64(v),
+                _ => HugValue::String(s.to_string()),
+            },
+        })
+    }
+}
+
+/// Matches two values of the same numeric type and divides them with `$int_op` for integers
+/// or `$float_op` for floats. Dividing an integer by zero gives [`EvalError::DivideByZero`],
+/// `$int_op` is only used for other divisors.
+macro_rules! checked_division {
+    ($lhs:expr, $rhs:expr, $int_op:ident, $float_op:tt) => {{
+        macro_rules! int_op {
+            ($variant:ident, $a:expr, $b:expr) => {
+                if *$b == 0 {
+                    Err(EvalError::DivideByZero)
+                } else {
+                    Ok(HugValue::$variant($a.$int_op(*$b)))
+                }
+            };
+        }
+
+        match ($lhs, $rhs) {
+            (HugValue::Int8(a), HugValue::Int8(b)) => int_op!(Int8, a, b),
+            (HugValue::Int16(a), HugValue::Int16(b)) => int_op!(Int16, a, b),
+            (HugValue::Int32(a), HugValue::Int32(b)) => int_op!(Int32, a, b),
+            (HugValue::Int64(a), HugValue::Int64(b)) => int_op!(Int64, a, b),
+            (HugValue::Int128(a), HugValue::Int128(b)) => int_op!(Int128, a, b),
+            (HugValue::UInt8(a), HugValue::UInt8(b)) => int_op!(UInt8, a, b),
+            (HugValue::UInt16(a), HugValue::UInt16(b)) => int_op!(UInt16, a, b),
+            (HugValue::UInt32(a), HugValue::UInt32(b)) => int_op!(UInt32, a, b),
+            (HugValue::UInt64(a), HugValue::UInt64(b)) => int_op!(UInt64, a, b),
+            (HugValue::UInt128(a), HugValue::UInt128(b)) => int_op!(UInt128, a, b),
+            #[cfg(feature = "big-int")]
+            (HugValue::BigInt(_), HugValue::BigInt(b)) if b.sign() == Sign::NoSign => {
+                Err(EvalError::DivideByZero)
+            }
+            #[cfg(feature = "big-int")]
+            (HugValue::BigInt(a), HugValue::BigInt(b)) => Ok(HugValue::BigInt(a $float_op b)),
+            (HugValue::Float32(a), HugValue::Float32(b)) => Ok(HugValue::Float32(a $float_op b)),
+            (HugValue::Float64(a), HugValue::Float64(b)) => Ok(HugValue::Float64(a $float_op b)),
+            _ => Err(TypeError::operands($lhs, $rhs).into()),
+        }
+    }};
+}
+
 impl HugValue {
-    pub fn assert<T: FromHugValue>(&self) -> Option<T> {
+    /// How deep [`HugValue::pretty`] walks into nested collections before writing `...`.
+    pub const PRETTY_MAX_DEPTH: usize = 8;
+
+    /// The type of this value.
+    pub fn kind(&self) -> TypeKind {
+        match self {
+            HugValue::Unit => TypeKind::Unit,
+            HugValue::Bool(_) => TypeKind::Bool,
+            HugValue::Int8(_) => TypeKind::Int8,
+            HugValue::Int16(_) => TypeKind::Int16,
+            HugValue::Int32(_) => TypeKind::Int32,
+            HugValue::Int64(_) => TypeKind::Int64,
+            HugValue::Int128(_) => TypeKind::Int128,
+            HugValue::UInt8(_) => TypeKind::UInt8,
+            HugValue::UInt16(_) => TypeKind::UInt16,
+            HugValue::UInt32(_) => TypeKind::UInt32,
+            HugValue::UInt64(_) => TypeKind::UInt64,
+            HugValue::UInt128(_) => TypeKind::UInt128,
+            #[cfg(feature = "big-int")]
+            HugValue::BigInt(_) => TypeKind::BigInt,
+            HugValue::Float32(_) => TypeKind::Float32,
+            HugValue::Float64(_) => TypeKind::Float64,
+            HugValue::String(_) => TypeKind::String,
+            HugValue::Array(_) => TypeKind::Array,
+            HugValue::Map(_) => TypeKind::Map,
+            HugValue::Struct { type_name, .. } => TypeKind::Other(*type_name),
+            HugValue::Function(_) => TypeKind::Function,
+            HugValue::ExternalFunction(_) => TypeKind::ExternalFunction,
+        }
+    }
+
+    pub fn assert<T: FromHugValue>(&self) -> Result<T, TypeError> {
         T::from_hug_value(self.clone())
     }
+
+    /// Returns the value stored under `key`, or `None` if the key is missing or this is not a map.
+    pub fn map_get(&self, key: &str) -> Option<&HugValue> {
+        if let HugValue::Map(map) = self {
+            map.get(key)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value of `field`, or `None` if the field is missing or this is not a struct.
+    pub fn field(&self, field: Ident) -> Option<&HugValue> {
+        if let HugValue::Struct { fields, .. } = self {
+            fields.get(&field)
+        } else {
+            None
+        }
+    }
+
+    /// Inserts `value` under `key`, returning the value it replaced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is not a map.
+    pub fn map_insert(&mut self, key: String, value: HugValue) -> Option<HugValue> {
+        if let HugValue::Map(map) = self {
+            map.insert(key, value)
+        } else {
+            panic!("Cannot insert a key into {:?}, it is not a map!", self)
+        }
+    }
+
+    /// Removes `key`, returning its value if it was present and this is a map.
+    pub fn map_remove(&mut self, key: &str) -> Option<HugValue> {
+        if let HugValue::Map(map) = self {
+            map.remove(key)
+        } else {
+            None
+        }
+    }
+
+    pub fn map_contains_key(&self, key: &str) -> bool {
+        self.map_get(key).is_some()
+    }
+
+    /// Merges the entries of `other` into this map. Keys present in both maps keep the value from
+    /// `other` if `overwrite` is set, and the value from this map otherwise.
+    pub fn map_merge(self, other: HugValue, overwrite: bool) -> Result<HugValue, TypeError> {
+        match (self, other) {
+            (HugValue::Map(mut map), HugValue::Map(other)) => {
+                for (key, value) in other {
+                    if overwrite || !map.contains_key(&key) {
+                        map.insert(key, value);
+                    }
+                }
+                Ok(HugValue::Map(map))
+            }
+            (HugValue::Map(_), other) => Err(TypeError::new("Map", &other)),
+            (other, _) => Err(TypeError::new("Map", &other)),
+        }
+    }
+
+    /// Checks if this is a function, either defined in a script or provided by a module.
+    pub fn is_callable(&self) -> bool {
+        matches!(self, HugValue::Function(_) | HugValue::ExternalFunction(_))
+    }
+
+    /// Returns how this function has to be called, or [`CallError::NotCallable`] if this isn't
+    /// a function.
+    pub fn callee(&self) -> Result<Callee, CallError> {
+        match self {
+            HugValue::Function(target) => Ok(Callee::Interpreted(*target)),
+            HugValue::ExternalFunction(function) => Ok(Callee::External(*function)),
+            _ => Err(CallError::NotCallable),
+        }
+    }
+
+    /// Calls an external function with `args`. Functions defined in a script can't be called
+    /// from here, they have to be run by the interpreter and give [`CallError::NotExternal`].
+    pub fn call_external(&self, args: Vec<HugValue>) -> Result<Option<HugValue>, CallError> {
+        match self.callee()? {
+            Callee::External(function) => Ok(function(args.into_iter())),
+            Callee::Interpreted(_) => Err(CallError::NotExternal),
+        }
+    }
+
+    /// Checks if this value counts as true in a condition. Values aren't converted to bools
+    /// implicitly, so like with `!`, anything other than a bool is a [`TypeError`].
+    pub fn is_truthy(&self) -> Result<bool, TypeError> {
+        match self {
+            HugValue::Bool(v) => Ok(*v),
+            other => Err(TypeError::new("Bool", other)),
+        }
+    }
+
+    /// Implements `&&`, `rhs` evaluates the right operand and is only called if this value is
+    /// true. Both operands have to be bools, see [`HugValue::is_truthy`].
+    pub fn and_then<E: From<TypeError>>(
+        &self,
+        rhs: impl FnOnce() -> Result<HugValue, E>,
+    ) -> Result<HugValue, E> {
+        if !self.is_truthy()? {
+            return Ok(HugValue::Bool(false));
+        }
+        Ok(HugValue::Bool(rhs()?.is_truthy()?))
+    }
+
+    /// Implements `||`, `rhs` evaluates the right operand and is only called if this value is
+    /// false. Both operands have to be bools, see [`HugValue::is_truthy`].
+    pub fn or_else<E: From<TypeError>>(
+        &self,
+        rhs: impl FnOnce() -> Result<HugValue, E>,
+    ) -> Result<HugValue, E> {
+        if self.is_truthy()? {
+            return Ok(HugValue::Bool(true));
+        }
+        Ok(HugValue::Bool(rhs()?.is_truthy()?))
+    }
+
+    /// Checks if this is any of the integer or float types.
+    pub fn is_number(&self) -> bool {
+        match self {
+            HugValue::Int8(_)
+            | HugValue::Int16(_)
+            | HugValue::Int32(_)
+            | HugValue::Int64(_)
+            | HugValue::Int128(_)
+            | HugValue::UInt8(_)
+            | HugValue::UInt16(_)
+            | HugValue::UInt32(_)
+            | HugValue::UInt64(_)
+            | HugValue::UInt128(_)
+            | HugValue::Float32(_)
+            | HugValue::Float64(_) => true,
+            #[cfg(feature = "big-int")]
+            HugValue::BigInt(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Converts any number to an `f64`, no matter its exact type. Integers that don't fit
+    /// exactly are rounded to the nearest `f64`, and big integers outside of its range become
+    /// infinite. Returns `None` for anything that isn't a number, unlike
+    /// [`HugValue::assert`] which only accepts a [`HugValue::Float64`].
+    pub fn as_f64(&self) -> Option<f64> {
+        Some(match *self {
+            HugValue::Int8(v) => v as f64,
+            HugValue::Int16(v) => v as f64,
+            HugValue::Int32(v) => v as f64,
+            HugValue::Int64(v) => v as f64,
+            HugValue::Int128(v) => v as f64,
+            HugValue::UInt8(v) => v as f64,
+            HugValue::UInt16(v) => v as f64,
+            HugValue::UInt32(v) => v as f64,
+            HugValue::UInt64(v) => v as f64,
+            HugValue::UInt128(v) => v as f64,
+            #[cfg(feature = "big-int")]
+            HugValue::BigInt(ref v) => v.to_f64()?,
+            HugValue::Float32(v) => v as f64,
+            HugValue::Float64(v) => v,
+            _ => return None,
+        })
+    }
+
+    /// Converts any number to an `i64`, no matter its exact type. Values outside of the range
+    /// of an `i64` saturate to [`i64::MIN`] or [`i64::MAX`], floats are truncated towards zero
+    /// and `NaN` becomes 0. Returns `None` for anything that isn't a number, unlike
+    /// [`HugValue::assert`] which only accepts a [`HugValue::Int64`].
+    pub fn as_i64(&self) -> Option<i64> {
+        Some(match *self {
+            HugValue::Int8(v) => v.into(),
+            HugValue::Int16(v) => v.into(),
+            HugValue::Int32(v) => v.into(),
+            HugValue::Int64(v) => v,
+            HugValue::Int128(v) => v.clamp(i64::MIN.into(), i64::MAX.into()) as i64,
+            HugValue::UInt8(v) => v.into(),
+            HugValue::UInt16(v) => v.into(),
+            HugValue::UInt32(v) => v.into(),
+            HugValue::UInt64(v) => i64::try_from(v).unwrap_or(i64::MAX),
+            HugValue::UInt128(v) => i64::try_from(v).unwrap_or(i64::MAX),
+            #[cfg(feature = "big-int")]
+            HugValue::BigInt(ref v) => v.to_i64().unwrap_or(match v.sign() {
+                Sign::Minus => i64::MIN,
+                _ => i64::MAX,
+            }),
+            HugValue::Float32(v) => v as i64,
+            HugValue::Float64(v) => v as i64,
+            _ => return None,
+        })
+    }
+
+    /// Estimates how many bytes this value owns on the heap, counting the full capacity of
+    /// strings and collections and everything nested in them. The value itself isn't counted,
+    /// as it might not be on the heap. Map entries are counted without the table's overhead.
+    pub fn heap_size(&self) -> usize {
+        match self {
+            HugValue::String(v) => v.capacity(),
+            HugValue::Array(array) => {
+                array.capacity() * std::mem::size_of::<HugValue>()
+                    + array.iter().map(HugValue::heap_size).sum::<usize>()
+            }
+            HugValue::Map(map) => {
+                map.capacity() * std::mem::size_of::<(String, HugValue)>()
+                    + map
+                        .iter()
+                        .map(|(key, value)| key.capacity() + value.heap_size())
+                        .sum::<usize>()
+            }
+            HugValue::Struct { fields, .. } => {
+                fields.capacity() * std::mem::size_of::<(Ident, HugValue)>()
+                    + fields.values().map(HugValue::heap_size).sum::<usize>()
+            }
+            #[cfg(feature = "big-int")]
+            HugValue::BigInt(v) => (v.bits() as usize).div_ceil(64) * 8,
+            _ => 0,
+        }
+    }
+
+    /// Returns both strings, or a [`TypeError`] if either value isn't a string.
+    fn strings<'a>(&'a self, other: &'a HugValue) -> Result<(&'a str, &'a str), TypeError> {
+        match (self, other) {
+            (HugValue::String(a), HugValue::String(b)) => Ok((a, b)),
+            (HugValue::String(_), other) | (other, _) => Err(TypeError::new("String", other)),
+        }
+    }
+
+    /// Checks if two strings are equal when ignoring case, using Unicode lowercase.
+    pub fn str_eq_ignore_case(&self, other: &HugValue) -> Result<bool, TypeError> {
+        let (a, b) = self.strings(other)?;
+        Ok(a.to_lowercase() == b.to_lowercase())
+    }
+
+    /// Checks if `pattern` appears anywhere in this string.
+    pub fn str_contains(&self, pattern: &HugValue) -> Result<bool, TypeError> {
+        let (a, b) = self.strings(pattern)?;
+        Ok(a.contains(b))
+    }
+
+    pub fn str_starts_with(&self, prefix: &HugValue) -> Result<bool, TypeError> {
+        let (a, b) = self.strings(prefix)?;
+        Ok(a.starts_with(b))
+    }
+
+    /// Like [`ToString::to_string`], but writes every element of a collection on its own line,
+    /// indented by `indent` spaces per level. Collections nested deeper than
+    /// [`HugValue::PRETTY_MAX_DEPTH`] are written as `[...]` or `{...}`.
+    pub fn pretty(&self, indent: usize) -> String {
+        self.pretty_with_depth(indent, HugValue::PRETTY_MAX_DEPTH)
+    }
+
+    /// [`HugValue::pretty`] with a custom maximum depth, a `max_depth` of 0 only writes
+    /// the outer collection's brackets.
+    pub fn pretty_with_depth(&self, indent: usize, max_depth: usize) -> String {
+        let mut buffer = String::new();
+        self.write_pretty(&mut buffer, indent, 0, max_depth);
+        buffer
+    }
+
+    fn write_pretty(&self, buffer: &mut String, indent: usize, depth: usize, max_depth: usize) {
+        let (open, close, elements) = match self {
+            HugValue::Array(array) => (
+                '[',
+                ']',
+                array.iter().map(|v| (None, v)).collect::<Vec<_>>(),
+            ),
+            HugValue::Map(map) => {
+                // Sorted so the same map is always written the same way
+                let mut entries: Vec<_> = map.iter().map(|(k, v)| (Some(k), v)).collect();
+                entries.sort_by_key(|(key, _)| *key);
+                ('{', '}', entries)
+            }
+            other => {
+                buffer.push_str(&other.to_string());
+                return;
+            }
+        };
+
+        buffer.push(open);
+        if !elements.is_empty() && depth >= max_depth {
+            buffer.push_str("...");
+        } else if !elements.is_empty() {
+            let padding = " ".repeat(indent * (depth + 1));
+            for (i, (key, value)) in elements.into_iter().enumerate() {
+                buffer.push_str(if i == 0 { "\n" } else { ",\n" });
+                buffer.push_str(&padding);
+                if let Some(key) = key {
+                    buffer.push_str(&format!("{:?}: ", key));
+                }
+                value.write_pretty(buffer, indent, depth + 1, max_depth);
+            }
+            buffer.push('\n');
+            buffer.push_str(&" ".repeat(indent * depth));
+        }
+        buffer.push(close);
+    }
+
+    /// Compares two values by their contents, walking into collections. Values of
+    /// different variants are never equal, even if they hold the same number.
+    pub fn deep_eq(&self, other: &HugValue) -> bool {
+        match (self, other) {
+            (HugValue::Unit, HugValue::Unit) => true,
+            (HugValue::Bool(a), HugValue::Bool(b)) => a == b,
+            (HugValue::Int8(a), HugValue::Int8(b)) => a == b,
+            (HugValue::Int16(a), HugValue::Int16(b)) => a == b,
+            (HugValue::Int32(a), HugValue::Int32(b)) => a == b,
+            (HugValue::Int64(a), HugValue::Int64(b)) => a == b,
+            (HugValue::Int128(a), HugValue::Int128(b)) => a == b,
+            (HugValue::UInt8(a), HugValue::UInt8(b)) => a == b,
+            (HugValue::UInt16(a), HugValue::UInt16(b)) => a == b,
+            (HugValue::UInt32(a), HugValue::UInt32(b)) => a == b,
+            (HugValue::UInt64(a), HugValue::UInt64(b)) => a == b,
+            (HugValue::UInt128(a), HugValue::UInt128(b)) => a == b,
+            #[cfg(feature = "big-int")]
+            (HugValue::BigInt(a), HugValue::BigInt(b)) => a == b,
+            (HugValue::Float32(a), HugValue::Float32(b)) => a == b,
+            (HugValue::Float64(a), HugValue::Float64(b)) => a == b,
+            (HugValue::String(a), HugValue::String(b)) => a == b,
+            (HugValue::Array(a), HugValue::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.deep_eq(b))
+            }
+            (HugValue::Map(a), HugValue::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(key, value)| b.get(key).is_some_and(|v| value.deep_eq(v)))
+            }
+            (
+                HugValue::Struct {
+                    type_name: a,
+                    fields: a_fields,
+                },
+                HugValue::Struct {
+                    type_name: b,
+                    fields: b_fields,
+                },
+            ) => {
+                a == b
+                    && a_fields.len() == b_fields.len()
+                    && a_fields
+                        .iter()
+                        .all(|(id, value)| b_fields.get(id).is_some_and(|v| value.deep_eq(v)))
+            }
+            (HugValue::Function(a), HugValue::Function(b)) => a == b,
+            (HugValue::ExternalFunction(a), HugValue::ExternalFunction(b)) => {
+                std::ptr::fn_addr_eq(*a, *b)
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks if `item` is in this collection, for maps this checks if `item` is one of its
+    /// keys. Returns `None` if this isn't a collection or `item` can't be in it.
+    pub fn contains(&self, item: &HugValue) -> Option<bool> {
+        match (self, item) {
+            (HugValue::Array(array), _) => Some(array.iter().any(|v| v.deep_eq(item))),
+            (HugValue::Map(_), HugValue::String(key)) => Some(self.map_contains_key(key)),
+            _ => None,
+        }
+    }
+
+    /// Returns the element at `index`, or `None` if it is out of range or this is not an array.
+    pub fn get(&self, index: usize) -> Option<&HugValue> {
+        if let HugValue::Array(array) = self {
+            array.get(index)
+        } else {
+            None
+        }
+    }
+
+    /// Creates a new array with the result of `f` for every element of this array.
+    pub fn array_map(&self, f: impl Fn(&HugValue) -> HugValue) -> Result<HugValue, TypeError> {
+        if let HugValue::Array(array) = self {
+            Ok(HugValue::Array(array.iter().map(f).collect()))
+        } else {
+            Err(TypeError::new("Array", self))
+        }
+    }
+
+    /// Creates a new array with only the elements of this array for which `f` returns `true`.
+    pub fn array_filter(&self, f: impl Fn(&HugValue) -> bool) -> Result<HugValue, TypeError> {
+        if let HugValue::Array(array) = self {
+            Ok(HugValue::Array(
+                array.iter().filter(|v| f(v)).cloned().collect(),
+            ))
+        } else {
+            Err(TypeError::new("Array", self))
+        }
+    }
+
+    /// Sorts this array in place with `by`, or with [`HugValue::compare`] if it is `None`.
+    /// Elements that can't be ordered, like values of different types or NaN with the default
+    /// order, give an error and leave the array as it was. Pass [`HugValue::total_cmp`] to
+    /// sort arrays with NaN in them. The sort is stable.
+    ///
+    /// Every element is compared with the first one before sorting, so `by` should only fail
+    /// for values that can't be ordered with every other value, like values of another type.
+    pub fn array_sort(&mut self, by: Option<CompareOp>) -> Result<(), TypeError> {
+        let array = match self {
+            HugValue::Array(array) => array,
+            other => return Err(TypeError::new("Array", other)),
+        };
+        let compare = by.unwrap_or(|a, b| {
+            a.compare(b)?
+                .ok_or_else(|| TypeError::new("a number that isn't NaN", a))
+        });
+
+        // sort_by can't stop at an error and panics if the order isn't total, so the elements
+        // are checked up front. Comparing the first one with itself catches a NaN there.
+        if let Some(first) = array.first() {
+            for element in array.iter() {
+                compare(first, element)?;
+            }
+        }
+        array.sort_by(|a, b| compare(a, b).expect("every element was checked before sorting"));
+        Ok(())
+    }
+
+    /// Checks if `self` and `rhs` are equal with [`HugValue::deep_eq`], comparing values of
+    /// different types is an error instead of just being false.
+    pub fn checked_eq(&self, rhs: &HugValue) -> Result<bool, TypeError> {
+        if std::mem::discriminant(self) == std::mem::discriminant(rhs) {
+            Ok(self.deep_eq(rhs))
+        } else {
+            Err(TypeError::new(self.kind().name(), rhs))
+        }
+    }
+
+    /// Orders `self` and `rhs` if both are the same number, string or bool type. Gives
+    /// `Ok(None)` if either is a NaN float, like [`PartialOrd::partial_cmp`]. This is what the
+    /// comparison operators use, so every comparison with NaN is false.
+    pub fn compare(&self, rhs: &HugValue) -> Result<Option<Ordering>, TypeError> {
+        self.compare_floats_with(rhs, false)
+    }
+
+    /// Like [`HugValue::compare`], but floats are ordered with [`f64::total_cmp`], so NaN is
+    /// ordered as well. Positive NaN sorts after infinity, and `-0.0` before `0.0`. Use this to
+    /// sort values, where every pair has to have an order.
+    pub fn total_cmp(&self, rhs: &HugValue) -> Result<Ordering, TypeError> {
+        self.compare_floats_with(rhs, true)
+            .map(|ordering| ordering.expect("every value is ordered when floats use total_cmp"))
+    }
+
+    /// Implements [`HugValue::compare`] and [`HugValue::total_cmp`], which only differ in how
+    /// they order floats.
+    fn compare_floats_with(
+        &self,
+        rhs: &HugValue,
+        total: bool,
+    ) -> Result<Option<Ordering>, TypeError> {
+        Ok(match (self, rhs) {
+            (HugValue::Bool(a), HugValue::Bool(b)) => a.partial_cmp(b),
+            (HugValue::Int8(a), HugValue::Int8(b)) => a.partial_cmp(b),
+            (HugValue::Int16(a), HugValue::Int16(b)) => a.partial_cmp(b),
+            (HugValue::Int32(a), HugValue::Int32(b)) => a.partial_cmp(b),
+            (HugValue::Int64(a), HugValue::Int64(b)) => a.partial_cmp(b),
+            (HugValue::Int128(a), HugValue::Int128(b)) => a.partial_cmp(b),
+            (HugValue::UInt8(a), HugValue::UInt8(b)) => a.partial_cmp(b),
+            (HugValue::UInt16(a), HugValue::UInt16(b)) => a.partial_cmp(b),
+            (HugValue::UInt32(a), HugValue::UInt32(b)) => a.partial_cmp(b),
+            (HugValue::UInt64(a), HugValue::UInt64(b)) => a.partial_cmp(b),
+            (HugValue::UInt128(a), HugValue::UInt128(b)) => a.partial_cmp(b),
+            #[cfg(feature = "big-int")]
+            (HugValue::BigInt(a), HugValue::BigInt(b)) => a.partial_cmp(b),
+            (HugValue::Float32(a), HugValue::Float32(b)) if total => Some(a.total_cmp(b)),
+            (HugValue::Float64(a), HugValue::Float64(b)) if total => Some(a.total_cmp(b)),
+            (HugValue::Float32(a), HugValue::Float32(b)) => a.partial_cmp(b),
+            (HugValue::Float64(a), HugValue::Float64(b)) => a.partial_cmp(b),
+            (HugValue::String(a), HugValue::String(b)) => a.partial_cmp(b),
+            _ if self.kind() == rhs.kind() => {
+                return Err(TypeError::new("a number, string or bool", self))
+            }
+            _ => return Err(TypeError::new(self.kind().name(), rhs)),
+        })
+    }
+
+    /// Divides `self` by `rhs` if both are the same numeric type. Dividing an integer by zero
+    /// is an error, floats follow IEEE 754 and give an infinity or NaN instead. Like the other
+    /// operators, integers wrap around on overflow.
+    pub fn checked_div(&self, rhs: &HugValue) -> Result<HugValue, EvalError> {
+        checked_division!(self, rhs, wrapping_div, /)
+    }
+
+    /// The remainder of dividing `self` by `rhs`, with the same rules as
+    /// [`HugValue::checked_div`].
+    pub fn checked_rem(&self, rhs: &HugValue) -> Result<HugValue, EvalError> {
+        checked_division!(self, rhs, wrapping_rem, %)
+    }
+
+    /// Inverts every bit of an integer, like `~x`. Bools are inverted with [`Not`] instead.
+    pub fn bitwise_not(self) -> Result<HugValue, TypeError> {
+        Ok(match self {
+            HugValue::Int8(v) => HugValue::Int8(!v),
+            HugValue::Int16(v) => HugValue::Int16(!v),
+            HugValue::Int32(v) => HugValue::Int32(!v),
+            HugValue::Int64(v) => HugValue::Int64(!v),
+            HugValue::Int128(v) => HugValue::Int128(!v),
+            HugValue::UInt8(v) => HugValue::UInt8(!v),
+            HugValue::UInt16(v) => HugValue::UInt16(!v),
+            HugValue::UInt32(v) => HugValue::UInt32(!v),
+            HugValue::UInt64(v) => HugValue::UInt64(!v),
+            HugValue::UInt128(v) => HugValue::UInt128(!v),
+            #[cfg(feature = "big-int")]
+            HugValue::BigInt(v) => HugValue::BigInt(!v),
+            other => return Err(TypeError::new("an integer", &other)),
+        })
+    }
+
+    /// Reads `self[index]`, currently only maps indexed by a string are supported. A missing
+    /// key gives `None`, leaving it up to the caller to either error or use a default.
+    pub fn index(&self, index: &HugValue) -> Option<&HugValue> {
+        match (self, index) {
+            (HugValue::Map(_), HugValue::String(key)) => self.map_get(key),
+            _ => None,
+        }
+    }
+
+    /// Reads `self[index]` for an array or a string indexed by any integer type, or a map
+    /// indexed by a string. Indexing a string gives the character at `index` as a string of
+    /// its own. An index past the end (or below zero) is an [`EvalError::IndexOutOfBounds`]
+    /// instead of a panic, and a key that isn't in the map is an [`EvalError::MissingKey`].
+    pub fn checked_index(&self, index: &HugValue) -> Result<HugValue, EvalError> {
+        if let HugValue::Map(_) = self {
+            return match index {
+                HugValue::String(key) => self
+                    .map_get(key)
+                    .cloned()
+                    .ok_or_else(|| EvalError::MissingKey(key.clone())),
+                _ => Err(TypeError::new("String", index).into()),
+            };
+        }
+
+        let position = match index {
+            HugValue::Int8(i) => usize::try_from(*i).ok(),
+            HugValue::Int16(i) => usize::try_from(*i).ok(),
+            HugValue::Int32(i) => usize::try_from(*i).ok(),
+            HugValue::Int64(i) => usize::try_from(*i).ok(),
+            HugValue::Int128(i) => usize::try_from(*i).ok(),
+            HugValue::UInt8(i) => Some(usize::from(*i)),
+            HugValue::UInt16(i) => Some(usize::from(*i)),
+            HugValue::UInt32(i) => usize::try_from(*i).ok(),
+            HugValue::UInt64(i) => usize::try_from(*i).ok(),
+            HugValue::UInt128(i) => usize::try_from(*i).ok(),
+            #[cfg(feature = "big-int")]
+            HugValue::BigInt(i) => usize::try_from(i).ok(),
+            _ => return Err(TypeError::new("an integer", index).into()),
+        };
+        let out_of_bounds = |len| EvalError::IndexOutOfBounds {
+            index: index.to_string(),
+            len,
+        };
+
+        match self {
+            HugValue::Array(array) => position
+                .and_then(|p| array.get(p))
+                .cloned()
+                .ok_or_else(|| out_of_bounds(array.len())),
+            HugValue::String(string) => position
+                .and_then(|p| string.chars().nth(p))
+                .map(|c| HugValue::String(c.to_string()))
+                .ok_or_else(|| out_of_bounds(string.chars().count())),
+            _ => Err(TypeError::new("an array, string or map", self).into()),
+        }
+    }
+}
+
+impl Index<usize> for HugValue {
+    type Output = HugValue;
+
+    /// Returns the element at `index` of an array, see [`HugValue::get`] for a version that
+    /// doesn't panic.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is not an array or `index` is out of range.
+    fn index(&self, index: usize) -> &HugValue {
+        match self {
+            HugValue::Array(array) => &array[index],
+            other => panic!("Cannot index into {:?}, it is not an array!", other),
+        }
+    }
+}
+
+/// Implements an arithmetic operator for two values of the same numeric type, any other
+/// combination is a [`TypeError`]. Integers wrap around on overflow like they do in release
+/// builds of Rust, big integers never overflow. Extra match arms can be given for
+/// non-numeric operands.
+macro_rules! impl_arithmetic_for_HugValue {
+    (
+        $trait:ident, $method:ident, $int_op:ident, $float_op:tt
+        $(, $extra:pat $(if $guard:expr)? => $result:expr)*
+    ) => {
+        impl $trait for HugValue {
+            type Output = Result<HugValue, TypeError>;
+
+            fn $method(self, rhs: HugValue) -> Result<HugValue, TypeError> {
+                Ok(match (self, rhs) {
+                    $($extra $(if $guard)? => $result,)*
+                    (HugValue::Int8(a), HugValue::Int8(b)) => HugValue::Int8(a.$int_op(b)),
+                    (HugValue::Int16(a), HugValue::Int16(b)) => HugValue::Int16(a.$int_op(b)),
+                    (HugValue::Int32(a), HugValue::Int32(b)) => HugValue::Int32(a.$int_op(b)),
+                    (HugValue::Int64(a), HugValue::Int64(b)) => HugValue::Int64(a.$int_op(b)),
+                    (HugValue::Int128(a), HugValue::Int128(b)) => HugValue::Int128(a.$int_op(b)),
+                    (HugValue::UInt8(a), HugValue::UInt8(b)) => HugValue::UInt8(a.$int_op(b)),
+                    (HugValue::UInt16(a), HugValue::UInt16(b)) => HugValue::UInt16(a.$int_op(b)),
+                    (HugValue::UInt32(a), HugValue::UInt32(b)) => HugValue::UInt32(a.$int_op(b)),
+                    (HugValue::UInt64(a), HugValue::UInt64(b)) => HugValue::UInt64(a.$int_op(b)),
+                    (HugValue::UInt128(a), HugValue::UInt128(b)) => {
+                        HugValue::UInt128(a.$int_op(b))
+                    }
+                    #[cfg(feature = "big-int")]
+                    (HugValue::BigInt(a), HugValue::BigInt(b)) => HugValue::BigInt(a $float_op b),
+                    (HugValue::Float32(a), HugValue::Float32(b)) => HugValue::Float32(a $float_op b),
+                    (HugValue::Float64(a), HugValue::Float64(b)) => HugValue::Float64(a $float_op b),
+                    (lhs, rhs) => return Err(TypeError::operands(&lhs, &rhs)),
+                })
+            }
+        }
+    };
+}
+
+// Adding strings concatenates them. A number added to a string (on either side) is converted
+// with `to_string` first, so `"n=" + 5` is `"n=5"`. Other values can't be added to strings.
+// Adding maps merges them, keys of the right map override those of the left.
+impl_arithmetic_for_HugValue!(Add, add, wrapping_add, +,
+    (a @ HugValue::Map(_), b @ HugValue::Map(_)) => a.map_merge(b, true)?,
+    (HugValue::String(a), HugValue::String(b)) => HugValue::String(a + &b),
+    (HugValue::String(a), b) if b.is_number() => HugValue::String(a + &b.to_string()),
+    (a, HugValue::String(b)) if a.is_number() => HugValue::String(a.to_string() + &b)
+);
+impl_arithmetic_for_HugValue!(Sub, sub, wrapping_sub, -);
+impl_arithmetic_for_HugValue!(Mul, mul, wrapping_mul, *);
+
+/// Divides two values of the same numeric type, see [`HugValue::checked_div`]. Dividing an
+/// integer by zero is an [`EvalError::DivideByZero`] instead of a panic.
+impl Div for HugValue {
+    type Output = Result<HugValue, EvalError>;
+
+    fn div(self, rhs: HugValue) -> Result<HugValue, EvalError> {
+        self.checked_div(&rhs)
+    }
 }
+
+/// The remainder of dividing two values of the same numeric type, see
+/// [`HugValue::checked_rem`].
+impl Rem for HugValue {
+    type Output = Result<HugValue, EvalError>;
+
+    fn rem(self, rhs: HugValue) -> Result<HugValue, EvalError> {
+        self.checked_rem(&rhs)
+    }
+}
+
+/// Negates a signed integer (wrapping around like the other operators) or a float, unsigned
+/// integers can't be negated.
+impl Neg for HugValue {
+    type Output = Result<HugValue, TypeError>;
+
+    fn neg(self) -> Result<HugValue, TypeError> {
+        Ok(match self {
+            HugValue::Int8(v) => HugValue::Int8(v.wrapping_neg()),
+            HugValue::Int16(v) => HugValue::Int16(v.wrapping_neg()),
+            HugValue::Int32(v) => HugValue::Int32(v.wrapping_neg()),
+            HugValue::Int64(v) => HugValue::Int64(v.wrapping_neg()),
+            HugValue::Int128(v) => HugValue::Int128(v.wrapping_neg()),
+            #[cfg(feature = "big-int")]
+            HugValue::BigInt(v) => HugValue::BigInt(-v),
+            HugValue::Float32(v) => HugValue::Float32(-v),
+            HugValue::Float64(v) => HugValue::Float64(-v),
+            other => return Err(TypeError::new("a signed number", &other)),
+        })
+    }
+}
+
+/// Inverts a bool, any other value is a [`TypeError`].
+impl Not for HugValue {
+    type Output = Result<HugValue, TypeError>;
+
+    fn not(self) -> Result<HugValue, TypeError> {
+        match self {
+            HugValue::Bool(v) => Ok(HugValue::Bool(!v)),
+            other => Err(TypeError::new("Bool", &other)),
+        }
+    }
+}
+
+/// Implements a bitwise operator for two integers of the same type or two bools, any other
+/// combination is a [`TypeError`].
+macro_rules! impl_bitwise_for_HugValue {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl $trait for HugValue {
+            type Output = Result<HugValue, TypeError>;
+
+            fn $method(self, rhs: HugValue) -> Result<HugValue, TypeError> {
+                Ok(match (self, rhs) {
+                    (HugValue::Bool(a), HugValue::Bool(b)) => HugValue::Bool(a $op b),
+                    (HugValue::Int8(a), HugValue::Int8(b)) => HugValue::Int8(a $op b),
+                    (HugValue::Int16(a), HugValue::Int16(b)) => HugValue::Int16(a $op b),
+                    (HugValue::Int32(a), HugValue::Int32(b)) => HugValue::Int32(a $op b),
+                    (HugValue::Int64(a), HugValue::Int64(b)) => HugValue::Int64(a $op b),
+                    (HugValue::Int128(a), HugValue::Int128(b)) => HugValue::Int128(a $op b),
+                    (HugValue::UInt8(a), HugValue::UInt8(b)) => HugValue::UInt8(a $op b),
+                    (HugValue::UInt16(a), HugValue::UInt16(b)) => HugValue::UInt16(a $op b),
+                    (HugValue::UInt32(a), HugValue::UInt32(b)) => HugValue::UInt32(a $op b),
+                    (HugValue::UInt64(a), HugValue::UInt64(b)) => HugValue::UInt64(a $op b),
+                    (HugValue::UInt128(a), HugValue::UInt128(b)) => HugValue::UInt128(a $op b),
+                    #[cfg(feature = "big-int")]
+                    (HugValue::BigInt(a), HugValue::BigInt(b)) => HugValue::BigInt(a $op b),
+                    (lhs, rhs) if lhs.kind() == rhs.kind() => {
+                        return Err(TypeError::new("an integer or bool", &lhs))
+                    }
+                    (lhs, rhs) => return Err(TypeError::operands(&lhs, &rhs)),
+                })
+            }
+        }
+    };
+}
+
+impl_bitwise_for_HugValue!(BitAnd, bitand, &);
+impl_bitwise_for_HugValue!(BitOr, bitor, |);
+impl_bitwise_for_HugValue!(BitXor, bitxor, ^);
+
+/// Reads the amount of a shift, which can be any fixed size integer. The amount is cast to a
+/// `u32` like `as` does, and `wrapping_shl`/`wrapping_shr` then only keep its lowest bits, so
+/// together the amount is taken modulo the bit width of the shifted value (`1 << 33` is `2`
+/// for an `Int32` and `1 << -1` is `1 << 31`).
+fn shift_amount(amount: &HugValue) -> Option<u32> {
+    Some(match *amount {
+        HugValue::Int8(v) => v as u32,
+        HugValue::Int16(v) => v as u32,
+        HugValue::Int32(v) => v as u32,
+        HugValue::Int64(v) => v as u32,
+        HugValue::Int128(v) => v as u32,
+        HugValue::UInt8(v) => v as u32,
+        HugValue::UInt16(v) => v as u32,
+        HugValue::UInt32(v) => v,
+        HugValue::UInt64(v) => v as u32,
+        HugValue::UInt128(v) => v as u32,
+        _ => return None,
+    })
+}
+
+/// Implements a shift of a fixed size integer by any integer amount, see [`shift_amount`] for
+/// how amounts larger than the bit width wrap. Right shifts of signed integers keep the sign.
+/// Big integers can't be shifted, as there is no bit width to wrap the amount at.
+macro_rules! impl_shift_for_HugValue {
+    ($trait:ident, $method:ident, $int_op:ident) => {
+        impl $trait for HugValue {
+            type Output = Result<HugValue, TypeError>;
+
+            fn $method(self, rhs: HugValue) -> Result<HugValue, TypeError> {
+                let amount = match shift_amount(&rhs) {
+                    Some(amount) => amount,
+                    None => return Err(TypeError::new("a fixed size integer", &rhs)),
+                };
+
+                Ok(match self {
+                    HugValue::Int8(v) => HugValue::Int8(v.$int_op(amount)),
+                    HugValue::Int16(v) => HugValue::Int16(v.$int_op(amount)),
+                    HugValue::Int32(v) => HugValue::Int32(v.$int_op(amount)),
+                    HugValue::Int64(v) => HugValue::Int64(v.$int_op(amount)),
+                    HugValue::Int128(v) => HugValue::Int128(v.$int_op(amount)),
+                    HugValue::UInt8(v) => HugValue::UInt8(v.$int_op(amount)),
+                    HugValue::UInt16(v) => HugValue::UInt16(v.$int_op(amount)),
+                    HugValue::UInt32(v) => HugValue::UInt32(v.$int_op(amount)),
+                    HugValue::UInt64(v) => HugValue::UInt64(v.$int_op(amount)),
+                    HugValue::UInt128(v) => HugValue::UInt128(v.$int_op(amount)),
+                    other => return Err(TypeError::new("a fixed size integer", &other)),
+                })
+            }
+        }
+    };
+}
+
+impl_shift_for_HugValue!(Shl, shl, wrapping_shl);
+impl_shift_for_HugValue!(Shr, shr, wrapping_shr);
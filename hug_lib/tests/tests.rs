@@ -0,0 +1,858 @@
+use std::{cmp::Ordering, collections::HashMap, ops::Add};
+
+use hug_lib::{
+    error::{CallError, EvalError, TypeError},
+    span::Spanned,
+    value::{Callee, FromHugValue, HugValue, TypeKind},
+};
+
+fn type_error(expected: &'static str, found: TypeKind) -> TypeError {
+    TypeError { expected, found }
+}
+
+#[test]
+fn map_operations() {
+    let mut map = HugValue::from(HashMap::new());
+
+    assert!(map.map_insert("a".to_string(), HugValue::from(1)).is_none());
+    assert!(map.map_contains_key("a"));
+    assert_eq!(map.map_get("a").unwrap().assert::<i32>(), Ok(1));
+
+    let replaced = map.map_insert("a".to_string(), HugValue::from(2));
+    assert_eq!(replaced.unwrap().assert::<i32>(), Ok(1));
+
+    assert_eq!(map.map_remove("a").unwrap().assert::<i32>(), Ok(2));
+    assert!(!map.map_contains_key("a"));
+    assert!(map.map_get("a").is_none());
+    assert!(map.map_remove("a").is_none());
+
+    assert!(HugValue::from(5).map_get("a").is_none());
+}
+
+#[test]
+fn map_merge() {
+    let mut a = HugValue::from(HashMap::new());
+    a.map_insert("a".to_string(), HugValue::from(1));
+    a.map_insert("b".to_string(), HugValue::from(2));
+    let mut b = HugValue::from(HashMap::new());
+    b.map_insert("b".to_string(), HugValue::from(3));
+    b.map_insert("c".to_string(), HugValue::from(4));
+
+    let sum = (a.clone() + b.clone()).unwrap();
+    assert_eq!(sum.map_get("a").unwrap().assert::<i32>(), Ok(1));
+    assert_eq!(sum.map_get("b").unwrap().assert::<i32>(), Ok(3));
+    assert_eq!(sum.map_get("c").unwrap().assert::<i32>(), Ok(4));
+
+    let kept = a.clone().map_merge(b, false).unwrap();
+    assert_eq!(kept.map_get("b").unwrap().assert::<i32>(), Ok(2));
+    assert_eq!(kept.map_get("c").unwrap().assert::<i32>(), Ok(4));
+
+    assert_eq!(
+        a.clone().add(HugValue::from(1)).unwrap_err(),
+        type_error("Map", TypeKind::Int32)
+    );
+    assert_eq!(
+        a.clone().map_merge(HugValue::from(1), true).unwrap_err(),
+        type_error("Map", TypeKind::Int32)
+    );
+    assert_eq!(
+        HugValue::from(1).map_merge(a, true).unwrap_err(),
+        type_error("Map", TypeKind::Int32)
+    );
+}
+
+#[test]
+fn map_index() {
+    let mut map = HugValue::from(HashMap::new());
+    map.map_insert("key".to_string(), HugValue::from(10));
+
+    let present = map.index(&HugValue::from("key".to_string()));
+    assert_eq!(present.unwrap().assert::<i32>(), Ok(10));
+
+    assert!(map.index(&HugValue::from("missing".to_string())).is_none());
+    assert!(map.index(&HugValue::from(0)).is_none());
+
+    let key = |key: &str| HugValue::from(key.to_string());
+    assert_eq!(map.checked_index(&key("key")), Ok(HugValue::from(10)));
+    assert_eq!(
+        map.checked_index(&key("missing")),
+        Err(EvalError::MissingKey("missing".to_string()))
+    );
+    assert!(map.checked_index(&HugValue::from(0)).is_err());
+}
+
+#[test]
+fn map_deep_eq() {
+    let mut inner = HashMap::new();
+    inner.insert("x".to_string(), HugValue::from(1));
+
+    let mut a = HugValue::from(HashMap::new());
+    a.map_insert("name".to_string(), HugValue::from("hug".to_string()));
+    a.map_insert("inner".to_string(), HugValue::from(inner.clone()));
+
+    let b = HugValue::from(HashMap::from([
+        ("inner".to_string(), HugValue::from(inner)),
+        ("name".to_string(), HugValue::from("hug".to_string())),
+    ]));
+    assert!(a.deep_eq(&b));
+
+    let mut c = b.clone();
+    c.map_insert("name".to_string(), HugValue::from("gub".to_string()));
+    assert!(!a.deep_eq(&c));
+
+    assert!(!HugValue::from(1i32).deep_eq(&HugValue::from(1i64)));
+}
+
+#[test]
+fn map_contains() {
+    let map = HugValue::from(HashMap::from([("a".to_string(), HugValue::from(1))]));
+
+    assert_eq!(map.contains(&HugValue::from("a".to_string())), Some(true));
+    assert_eq!(map.contains(&HugValue::from("b".to_string())), Some(false));
+    assert_eq!(map.contains(&HugValue::from(1)), None);
+    assert_eq!(HugValue::from(1).contains(&HugValue::from(1)), None);
+}
+
+#[test]
+fn divide_by_zero() {
+    assert_eq!(
+        HugValue::from(1)
+            .checked_div(&HugValue::from(0))
+            .unwrap_err(),
+        EvalError::DivideByZero
+    );
+    assert_eq!(
+        HugValue::from(1)
+            .checked_div(&HugValue::from(0u8))
+            .unwrap_err(),
+        EvalError::TypeError(type_error("Int32", TypeKind::UInt8))
+    );
+    assert!(matches!(
+        HugValue::from(6).checked_div(&HugValue::from(3)),
+        Ok(HugValue::Int32(2))
+    ));
+}
+
+#[test]
+fn booleans() {
+    assert!(matches!(HugValue::from(true), HugValue::Bool(true)));
+    assert_eq!(HugValue::from(false).assert::<bool>(), Ok(false));
+    assert_eq!(
+        HugValue::from(1).assert::<bool>(),
+        Err(type_error("Bool", TypeKind::Int32))
+    );
+    assert!(HugValue::from(true).deep_eq(&HugValue::from(true)));
+    assert!(!HugValue::from(true).deep_eq(&HugValue::from(1)));
+}
+
+#[test]
+fn type_errors() {
+    let error = HugValue::from(5).assert::<String>().unwrap_err();
+    assert_eq!(error.expected, "String");
+    assert_eq!(error.found, TypeKind::Int32);
+    assert_eq!(error.to_string(), "expected String, found Int32");
+
+    assert_eq!(HugValue::from(vec![HugValue::Unit]).kind(), TypeKind::Array);
+    assert_eq!(
+        TypeKind::from_name(TypeKind::UInt16.name()),
+        Some(TypeKind::UInt16)
+    );
+}
+
+#[test]
+fn to_string() {
+    assert_eq!(HugValue::from(true).to_string(), "true");
+    assert_eq!(HugValue::Unit.to_string(), "none");
+    assert_eq!(
+        HugValue::from(vec![HugValue::from(1), HugValue::from("a".to_string())]).to_string(),
+        "[1, a]"
+    );
+
+    let mut map = HugValue::from(HashMap::new());
+    map.map_insert("b".to_string(), HugValue::from(2));
+    map.map_insert("a".to_string(), HugValue::from(1.5f64));
+    assert_eq!(map.to_string(), "{\"a\": 1.5, \"b\": 2}");
+
+    fn noop(_: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+        None
+    }
+    assert_eq!(HugValue::Function(4).to_string(), "function@4");
+    assert_eq!(
+        HugValue::ExternalFunction(noop).to_string(),
+        "external function"
+    );
+}
+
+#[test]
+fn pretty() {
+    let mut map = HugValue::from(HashMap::new());
+    map.map_insert("b".to_string(), HugValue::from(Vec::new()));
+    map.map_insert(
+        "a".to_string(),
+        HugValue::from(vec![HugValue::from(1), HugValue::from(2)]),
+    );
+    let value = HugValue::from(vec![HugValue::from(true), map]);
+
+    assert_eq!(
+        value.pretty(2),
+        "[\n  true,\n  {\n    \"a\": [\n      1,\n      2\n    ],\n    \"b\": []\n  }\n]"
+    );
+    assert_eq!(value.pretty_with_depth(4, 1), "[\n    true,\n    {...}\n]");
+    assert_eq!(value.pretty_with_depth(4, 0), "[...]");
+    assert_eq!(HugValue::from(5).pretty(2), "5");
+}
+
+#[test]
+fn comparisons() {
+    assert_eq!(
+        HugValue::from(1u8).compare(&HugValue::from(2u8)),
+        Ok(Some(Ordering::Less))
+    );
+    assert_eq!(
+        HugValue::from(f64::NAN).compare(&HugValue::from(1.0f64)),
+        Ok(None)
+    );
+    assert_eq!(
+        HugValue::from(1).compare(&HugValue::from(1u8)),
+        Err(type_error("Int32", TypeKind::UInt8))
+    );
+    assert_eq!(
+        HugValue::from(vec![HugValue::from(1)])
+            .checked_eq(&HugValue::from(vec![HugValue::from(1)])),
+        Ok(true)
+    );
+    assert_eq!(
+        HugValue::Unit.checked_eq(&HugValue::from(false)),
+        Err(type_error("Unit", TypeKind::Bool))
+    );
+}
+
+#[test]
+fn short_circuit() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let rhs = |value: bool| {
+        calls.set(calls.get() + 1);
+        Ok::<_, TypeError>(HugValue::from(value))
+    };
+
+    let (yes, no) = (HugValue::from(true), HugValue::from(false));
+    assert_eq!(no.and_then(|| rhs(true)), Ok(no.clone()));
+    assert_eq!(yes.or_else(|| rhs(false)), Ok(yes.clone()));
+    assert_eq!(calls.get(), 0);
+
+    assert_eq!(yes.and_then(|| rhs(false)), Ok(no.clone()));
+    assert_eq!(no.or_else(|| rhs(true)), Ok(yes.clone()));
+    assert_eq!(calls.get(), 2);
+
+    assert_eq!(
+        HugValue::from(1).and_then(|| rhs(true)),
+        Err(type_error("Bool", TypeKind::Int32))
+    );
+    assert_eq!(
+        yes.and_then(|| Ok::<_, TypeError>(HugValue::Unit)),
+        Err(type_error("Bool", TypeKind::Unit))
+    );
+    assert_eq!(
+        HugValue::Unit.is_truthy(),
+        Err(type_error("Bool", TypeKind::Unit))
+    );
+}
+
+#[test]
+fn equality() {
+    assert_eq!(HugValue::from(5), HugValue::from(5));
+    assert_ne!(HugValue::from(5), HugValue::from(6));
+    assert_ne!(HugValue::from(5), HugValue::from(5i64));
+    assert_ne!(HugValue::from(1), HugValue::from(true));
+    assert_eq!(
+        HugValue::from("hug".to_string()),
+        HugValue::from("hug".to_string())
+    );
+    assert_ne!(
+        HugValue::from("hug".to_string()),
+        HugValue::from("Hug".to_string())
+    );
+    assert_ne!(HugValue::from(f64::NAN), HugValue::from(f64::NAN));
+    assert_eq!(HugValue::Function(2), HugValue::Function(2));
+    assert_ne!(HugValue::Function(2), HugValue::Function(3));
+    assert_eq!(
+        HugValue::from(vec![HugValue::from(1), HugValue::Unit]),
+        HugValue::from(vec![HugValue::from(1), HugValue::Unit])
+    );
+}
+
+#[test]
+fn total_ordering() {
+    let nan = HugValue::from(f64::NAN);
+    assert_eq!(nan.compare(&nan), Ok(None));
+    assert_eq!(nan.total_cmp(&nan), Ok(Ordering::Equal));
+    assert_eq!(
+        nan.total_cmp(&HugValue::from(f64::INFINITY)),
+        Ok(Ordering::Greater)
+    );
+    assert_eq!(
+        HugValue::from(-0.0f32).total_cmp(&HugValue::from(0.0f32)),
+        Ok(Ordering::Less)
+    );
+    assert_eq!(
+        HugValue::from(-0.0f32).compare(&HugValue::from(0.0f32)),
+        Ok(Some(Ordering::Equal))
+    );
+    assert_eq!(
+        HugValue::from("b".to_string()).total_cmp(&HugValue::from("a".to_string())),
+        Ok(Ordering::Greater)
+    );
+    assert_eq!(
+        HugValue::from(1.0f64).total_cmp(&HugValue::from(1.0f32)),
+        Err(type_error("Float64", TypeKind::Float32))
+    );
+
+    let mut values = [
+        HugValue::from(2.0f64),
+        HugValue::from(f64::NAN),
+        HugValue::from(-1.0f64),
+    ];
+    values.sort_by(|a, b| a.total_cmp(b).unwrap());
+    let sorted: Vec<f64> = values.iter().map(|v| v.assert::<f64>().unwrap()).collect();
+    assert_eq!(sorted[..2], [-1.0, 2.0]);
+    assert!(sorted[2].is_nan());
+}
+
+#[test]
+fn string_helpers() {
+    let string = |s: &str| HugValue::from(s.to_string());
+
+    assert_eq!(string("Hug").str_eq_ignore_case(&string("hUG")), Ok(true));
+    assert_eq!(
+        string("Straße").str_eq_ignore_case(&string("STRASSE")),
+        Ok(false)
+    );
+    assert_eq!(string("ÄB").str_eq_ignore_case(&string("äb")), Ok(true));
+    assert_eq!(string("hug").str_eq_ignore_case(&string("hugs")), Ok(false));
+
+    assert_eq!(string("hello world").str_contains(&string("o w")), Ok(true));
+    assert_eq!(string("hello").str_contains(&string("")), Ok(true));
+    assert_eq!(string("hello").str_contains(&string("world")), Ok(false));
+
+    assert_eq!(string("hello").str_starts_with(&string("he")), Ok(true));
+    assert_eq!(string("hello").str_starts_with(&string("lo")), Ok(false));
+
+    assert_eq!(
+        HugValue::from(1).str_contains(&string("1")),
+        Err(type_error("String", TypeKind::Int32))
+    );
+    assert_eq!(
+        string("1").str_starts_with(&HugValue::from(1)),
+        Err(type_error("String", TypeKind::Int32))
+    );
+    assert_eq!(
+        HugValue::Unit.str_eq_ignore_case(&HugValue::Unit),
+        Err(type_error("String", TypeKind::Unit))
+    );
+}
+
+#[test]
+fn heap_size() {
+    let value_size = std::mem::size_of::<HugValue>();
+
+    assert_eq!(HugValue::from(5).heap_size(), 0);
+    assert_eq!(HugValue::from("hello".to_string()).heap_size(), 5);
+    assert_eq!(HugValue::from(String::with_capacity(16)).heap_size(), 16);
+
+    let nested = HugValue::from(vec![
+        HugValue::from(1),
+        HugValue::from(vec![HugValue::from("ab".to_string())]),
+    ]);
+    assert_eq!(nested.heap_size(), 2 * value_size + (value_size + 2));
+
+    let mut map = HugValue::from(HashMap::new());
+    assert_eq!(map.heap_size(), 0);
+    map.map_insert("key".to_string(), HugValue::from("value".to_string()));
+    assert!(map.heap_size() >= std::mem::size_of::<(String, HugValue)>() + 3 + 5);
+}
+
+#[test]
+fn arithmetic() {
+    assert!(matches!(
+        HugValue::from(2) + HugValue::from(3),
+        Ok(HugValue::Int32(5))
+    ));
+    assert!(matches!(
+        HugValue::from(7u64) % HugValue::from(4u64),
+        Ok(HugValue::UInt64(3))
+    ));
+    assert!(matches!(
+        HugValue::from(1.5f64) * HugValue::from(2.0f64),
+        Ok(HugValue::Float64(v)) if v == 3.0
+    ));
+    assert_eq!(
+        (HugValue::from(1) + HugValue::from(1.0f64)).unwrap_err(),
+        type_error("Int32", TypeKind::Float64)
+    );
+    assert!((HugValue::from("a".to_string()) - HugValue::from("b".to_string())).is_err());
+}
+
+#[test]
+fn negation() {
+    assert!(matches!(-HugValue::from(5), Ok(HugValue::Int32(-5))));
+    assert!(matches!(
+        -HugValue::from(i8::MIN),
+        Ok(HugValue::Int8(i8::MIN))
+    ));
+    assert!(matches!(-HugValue::from(1.5f64), Ok(HugValue::Float64(v)) if v == -1.5));
+    assert_eq!(
+        (-HugValue::from(5u32)).unwrap_err(),
+        type_error("a signed number", TypeKind::UInt32)
+    );
+
+    assert!(matches!(!HugValue::from(true), Ok(HugValue::Bool(false))));
+    assert_eq!(
+        (!HugValue::from(0)).unwrap_err(),
+        type_error("Bool", TypeKind::Int32)
+    );
+}
+
+#[test]
+fn bitwise() {
+    assert!(matches!(
+        HugValue::from(0xF0) & HugValue::from(0x0F),
+        Ok(HugValue::Int32(0))
+    ));
+    assert!(matches!(
+        HugValue::from(0b1010u8) | HugValue::from(0b0101u8),
+        Ok(HugValue::UInt8(0b1111))
+    ));
+    assert!(matches!(
+        HugValue::from(0u8).bitwise_not(),
+        Ok(HugValue::UInt8(u8::MAX))
+    ));
+    assert_eq!(
+        (HugValue::from(1) & HugValue::from(1u8)).unwrap_err(),
+        type_error("Int32", TypeKind::UInt8)
+    );
+
+    // Shift amounts can be any integer type and wrap at the bit width of the shifted value
+    assert!(matches!(
+        HugValue::from(1) << HugValue::from(4u8),
+        Ok(HugValue::Int32(16))
+    ));
+    assert!(matches!(
+        HugValue::from(1) << HugValue::from(33),
+        Ok(HugValue::Int32(2))
+    ));
+    assert!(matches!(
+        HugValue::from(1u8) << HugValue::from(-1),
+        Ok(HugValue::UInt8(0x80))
+    ));
+    assert!(matches!(
+        HugValue::from(0x80u8) >> HugValue::from(7i64),
+        Ok(HugValue::UInt8(1))
+    ));
+    assert_eq!(
+        (HugValue::from(1.0f32) << HugValue::from(1)).unwrap_err(),
+        type_error("a fixed size integer", TypeKind::Float32)
+    );
+    assert_eq!(
+        (HugValue::from(1) >> HugValue::from("1".to_string())).unwrap_err(),
+        type_error("a fixed size integer", TypeKind::String)
+    );
+}
+
+#[test]
+fn string_concatenation() {
+    let string = |s: &str| HugValue::from(s.to_string());
+    let concat = |a, b| match HugValue::add(a, b) {
+        Ok(HugValue::String(v)) => v,
+        other => panic!("Expected a string, got {:?}", other),
+    };
+
+    assert_eq!(concat(string("a"), string("b")), "ab");
+    assert_eq!(concat(string("n="), HugValue::from(5)), "n=5");
+    assert_eq!(concat(HugValue::from(1.5f64), string(" m")), "1.5 m");
+    assert_eq!(concat(string(""), HugValue::from(7u8)), "7");
+
+    assert_eq!(
+        (string("a") + HugValue::from(true)).unwrap_err(),
+        type_error("String", TypeKind::Bool)
+    );
+    assert_eq!(
+        (HugValue::Unit + string("a")).unwrap_err(),
+        type_error("Unit", TypeKind::String)
+    );
+    assert_eq!(
+        (string("a") * HugValue::from(3)).unwrap_err(),
+        type_error("String", TypeKind::Int32)
+    );
+}
+
+#[test]
+fn arithmetic_overflow() {
+    // Operators wrap around
+    assert!(matches!(
+        HugValue::from(i8::MAX) + HugValue::from(1i8),
+        Ok(HugValue::Int8(i8::MIN))
+    ));
+    assert!(matches!(
+        HugValue::from(0u32) - HugValue::from(1u32),
+        Ok(HugValue::UInt32(u32::MAX))
+    ));
+    assert!(matches!(
+        HugValue::from(i32::MIN) / HugValue::from(-1),
+        Ok(HugValue::Int32(i32::MIN))
+    ));
+
+    // The checked methods report errors instead
+    assert_eq!(
+        HugValue::from(7)
+            .checked_rem(&HugValue::from(0))
+            .unwrap_err(),
+        EvalError::DivideByZero
+    );
+    assert!(matches!(
+        HugValue::from(1.0f32).checked_div(&HugValue::from(0.0f32)),
+        Ok(HugValue::Float32(v)) if v.is_infinite()
+    ));
+}
+
+#[test]
+fn arithmetic_divide_by_zero() {
+    assert_eq!(
+        HugValue::from(1) / HugValue::from(0),
+        Err(EvalError::DivideByZero)
+    );
+    assert_eq!(
+        HugValue::from(1u64) % HugValue::from(0u64),
+        Err(EvalError::DivideByZero)
+    );
+    assert!(matches!(
+        HugValue::from(1.0) / HugValue::from(0.0),
+        Ok(HugValue::Float64(v)) if v.is_infinite()
+    ));
+    assert_eq!(
+        HugValue::from(1) / HugValue::from(1u8),
+        Err(EvalError::TypeError(type_error("Int32", TypeKind::UInt8)))
+    );
+}
+
+#[test]
+fn array_map_filter() {
+    let array = HugValue::from(vec![
+        HugValue::from(1),
+        HugValue::from(2),
+        HugValue::from(3),
+    ]);
+
+    let doubled = array
+        .array_map(|v| HugValue::from(v.assert::<i32>().unwrap() * 2))
+        .unwrap();
+    assert!(doubled.deep_eq(&HugValue::from(vec![
+        HugValue::from(2),
+        HugValue::from(4),
+        HugValue::from(6),
+    ])));
+
+    let evens = array
+        .array_filter(|v| v.assert::<i32>().unwrap() % 2 == 0)
+        .unwrap();
+    assert!(evens.deep_eq(&HugValue::from(vec![HugValue::from(2)])));
+    assert_eq!(array.contains(&HugValue::from(2)), Some(true));
+    assert_eq!(array.contains(&HugValue::from(2u8)), Some(false));
+
+    assert_eq!(
+        HugValue::from(1).array_map(|v| v.clone()).unwrap_err(),
+        type_error("Array", TypeKind::Int32)
+    );
+    assert_eq!(
+        HugValue::from(1).array_filter(|_| true).unwrap_err(),
+        type_error("Array", TypeKind::Int32)
+    );
+}
+
+#[test]
+fn array_index() {
+    let array = HugValue::from(vec![HugValue::from(1), HugValue::from(2)]);
+
+    assert!(matches!(array[1], HugValue::Int32(2)));
+    assert!(matches!(array.get(0), Some(HugValue::Int32(1))));
+    assert!(array.get(2).is_none());
+    assert!(HugValue::from(1).get(0).is_none());
+}
+
+#[test]
+fn checked_index() {
+    let array = HugValue::from(vec![HugValue::from(1), HugValue::from(2)]);
+    assert!(matches!(
+        array.checked_index(&HugValue::from(1u8)),
+        Ok(HugValue::Int32(2))
+    ));
+    assert_eq!(
+        array.checked_index(&HugValue::from(2)).unwrap_err(),
+        EvalError::IndexOutOfBounds {
+            index: "2".to_string(),
+            len: 2
+        }
+    );
+    assert_eq!(
+        array.checked_index(&HugValue::from(-1)).unwrap_err(),
+        EvalError::IndexOutOfBounds {
+            index: "-1".to_string(),
+            len: 2
+        }
+    );
+
+    let string = HugValue::from("héllo".to_string());
+    assert_eq!(
+        string
+            .checked_index(&HugValue::from(1))
+            .unwrap()
+            .to_string(),
+        "é"
+    );
+    assert_eq!(
+        string.checked_index(&HugValue::from(5)).unwrap_err(),
+        EvalError::IndexOutOfBounds {
+            index: "5".to_string(),
+            len: 5
+        }
+    );
+
+    assert_eq!(
+        array.checked_index(&HugValue::from(1.0)).unwrap_err(),
+        EvalError::TypeError(type_error("an integer", TypeKind::Float64))
+    );
+    assert_eq!(
+        HugValue::from(1)
+            .checked_index(&HugValue::from(0))
+            .unwrap_err(),
+        EvalError::TypeError(type_error("an array, string or map", TypeKind::Int32))
+    );
+}
+
+#[test]
+#[should_panic]
+fn array_index_not_an_array() {
+    let _ = &HugValue::from(1)[0];
+}
+
+#[test]
+fn prelude() {
+    use hug_lib::prelude::*;
+
+    fn convert<T: FromHugValue>(value: HugValue) -> Result<T, TypeError> {
+        T::from_hug_value(value)
+    }
+
+    assert_eq!(convert::<i32>(HugValue::from(5)), Ok(5));
+}
+
+#[test]
+fn fixed_size_conversions() {
+    let array = HugValue::from(vec![
+        HugValue::from(1),
+        HugValue::from(2),
+        HugValue::from(3),
+    ]);
+
+    assert_eq!(array.assert::<[i32; 3]>(), Ok([1, 2, 3]));
+    assert_eq!(
+        array.assert::<[i32; 2]>(),
+        Err(type_error(
+            "an array of a different length",
+            TypeKind::Array
+        ))
+    );
+    assert_eq!(
+        array.assert::<[u8; 3]>(),
+        Err(type_error("UInt8", TypeKind::Int32))
+    );
+    assert_eq!(array.assert::<(i32, i32, i32)>(), Ok((1, 2, 3)));
+    assert_eq!(
+        array.assert::<(i32, i32)>(),
+        Err(type_error(
+            "an array of a different length",
+            TypeKind::Array
+        ))
+    );
+
+    let pair = HugValue::from(vec![HugValue::from(1), HugValue::from("a".to_string())]);
+    assert_eq!(pair.assert::<(i32, String)>(), Ok((1, "a".to_string())));
+    assert_eq!(
+        HugValue::from(1).assert::<(i32, i32)>(),
+        Err(type_error("Array", TypeKind::Int32))
+    );
+}
+
+#[cfg(feature = "big-int")]
+#[test]
+fn big_integers() {
+    use hug_lib::value::{TypeKind, TypedDefinition};
+
+    let big = |value: &str| {
+        HugValue::parse_from_type(TypeKind::BigInt, value.to_string(), 0..value.len()).unwrap()
+    };
+
+    let max = big("170141183460469231731687303715884105727");
+    let sum = (max.clone() + max.clone()).unwrap();
+    assert_eq!(sum.to_string(), "340282366920938463463374607431768211454");
+    let power = big("0x1_0000_0000_0000_0000_0000_0000_0000_0000");
+    assert!(sum.deep_eq(&(power - big("2")).unwrap()));
+    assert!((max + HugValue::from(1)).is_err());
+    assert_eq!(
+        big("1").checked_div(&big("0")).unwrap_err(),
+        EvalError::DivideByZero
+    );
+
+    assert_eq!(big("-12").as_i64(), Some(-12));
+    assert_eq!(sum.as_i64(), Some(i64::MAX));
+    assert_eq!((big("0") - sum).unwrap().as_i64(), Some(i64::MIN));
+    assert_eq!(big("1024").as_f64(), Some(1024.0));
+}
+
+#[test]
+fn numeric_coercion() {
+    assert_eq!(HugValue::Int8(5).as_f64(), Some(5.0));
+    assert_eq!(
+        HugValue::UInt128(u128::MAX).as_f64(),
+        Some(u128::MAX as f64)
+    );
+    assert_eq!(HugValue::Float32(1.5).as_f64(), Some(1.5));
+
+    assert_eq!(HugValue::UInt16(7).as_i64(), Some(7));
+    assert_eq!(HugValue::Int128(i128::MIN).as_i64(), Some(i64::MIN));
+    assert_eq!(HugValue::UInt64(u64::MAX).as_i64(), Some(i64::MAX));
+    assert_eq!(HugValue::Float64(-2.7).as_i64(), Some(-2));
+    assert_eq!(HugValue::Float64(1e30).as_i64(), Some(i64::MAX));
+    assert_eq!(HugValue::Float32(f32::NAN).as_i64(), Some(0));
+
+    fn noop(_: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+        None
+    }
+    for value in [
+        HugValue::from("5".to_string()),
+        HugValue::from(true),
+        HugValue::Function(0),
+        HugValue::ExternalFunction(noop),
+        HugValue::Unit,
+    ] {
+        assert_eq!(value.as_f64(), None);
+        assert_eq!(value.as_i64(), None);
+    }
+}
+
+#[test]
+fn callables() {
+    fn first(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+        args.next()
+    }
+
+    let external = HugValue::ExternalFunction(first);
+    let interpreted = HugValue::Function(3);
+    assert!(external.is_callable() && interpreted.is_callable());
+    assert!(!HugValue::from(3).is_callable());
+
+    assert!(matches!(external.callee(), Ok(Callee::External(_))));
+    assert!(matches!(interpreted.callee(), Ok(Callee::Interpreted(3))));
+    assert!(matches!(
+        HugValue::from(3).callee(),
+        Err(CallError::NotCallable)
+    ));
+
+    let result = external.call_external(vec![HugValue::from(5)]).unwrap();
+    assert_eq!(result.unwrap().assert::<i32>(), Ok(5));
+    assert_eq!(
+        interpreted.call_external(Vec::new()).unwrap_err(),
+        CallError::NotExternal
+    );
+    assert_eq!(
+        HugValue::from(3).call_external(Vec::new()).unwrap_err(),
+        CallError::NotCallable
+    );
+    assert_eq!(
+        usize::from_hug_value(external),
+        Err(type_error("Function", TypeKind::ExternalFunction))
+    );
+}
+
+#[test]
+fn function_targets() {
+    let function = HugValue::from(3usize);
+    assert!(matches!(function, HugValue::Function(3)));
+    assert_eq!(function.assert::<usize>(), Ok(3));
+    assert_eq!(
+        HugValue::from(3).assert::<usize>(),
+        Err(type_error("Function", TypeKind::Int32))
+    );
+    assert_eq!(
+        HugValue::from(true).assert::<usize>(),
+        Err(type_error("Function", TypeKind::Bool))
+    );
+}
+
+#[test]
+fn from_str() {
+    let value = |text: &str| text.parse::<HugValue>().unwrap();
+
+    assert_eq!(value("true"), HugValue::Bool(true));
+    assert_eq!(value("false"), HugValue::Bool(false));
+    assert_eq!(value("42"), HugValue::Int32(42));
+    assert_eq!(value("-7"), HugValue::Int32(-7));
+    assert_eq!(value("007"), HugValue::Int32(7));
+    assert_eq!(value("1.5"), HugValue::Float64(1.5));
+    assert_eq!(value("-2e3"), HugValue::Float64(-2000.0));
+    assert_eq!(value("3000000000"), HugValue::Float64(3e9));
+    for text in [
+        "", "hi", "True", "0x10", "1_000", "NaN", "inf", " 5", "1.2.3",
+    ] {
+        assert_eq!(value(text), HugValue::String(text.to_string()));
+    }
+}
+
+#[test]
+fn spanned() {
+    let mut spanned = Spanned::new(String::from("hug"), 4..7);
+    assert_eq!(spanned.len(), 3);
+    assert_eq!(spanned.span(), 4..7);
+
+    spanned.push('s');
+    assert_eq!(*spanned, "hugs");
+
+    let spanned = spanned.map(|text| text.len());
+    assert_eq!(spanned, Spanned::new(4, 4..7));
+    assert_eq!(spanned.into_inner(), 4);
+}
+
+#[test]
+fn array_sort() {
+    let array = |values: &[HugValue]| HugValue::Array(values.to_vec());
+
+    let mut numbers = array(&[3.into(), (-1).into(), 2.into(), 0.into()]);
+    numbers.array_sort(None).unwrap();
+    assert_eq!(numbers, array(&[(-1).into(), 0.into(), 2.into(), 3.into()]));
+
+    let mut mixed = array(&[3.into(), "a".to_string().into(), 1.into()]);
+    assert!(mixed.array_sort(None).is_err());
+    assert_eq!(mixed, array(&[3.into(), "a".to_string().into(), 1.into()]));
+
+    let mut floats = array(&[1.5.into(), f64::NAN.into(), (-2.0).into()]);
+    assert!(floats.array_sort(None).is_err());
+    assert!(array(&[f64::NAN.into(), 1.0.into()])
+        .array_sort(None)
+        .is_err());
+    // Large enough for the sort to notice an order that isn't total
+    let mut many: Vec<HugValue> = (0..100).map(|i| HugValue::from(i as f64)).collect();
+    many[10] = f64::NAN.into();
+    many[60] = f64::NAN.into();
+    let mut many = HugValue::Array(many);
+    assert!(many.array_sort(None).is_err());
+    many.array_sort(Some(HugValue::total_cmp)).unwrap();
+    floats.array_sort(Some(HugValue::total_cmp)).unwrap();
+    assert_eq!(floats.get(0), Some(&HugValue::from(-2.0)));
+    assert!(matches!(floats.get(2), Some(HugValue::Float64(v)) if v.is_nan()));
+
+    assert_eq!(
+        HugValue::from(1).array_sort(None),
+        Err(TypeError::new("Array", &HugValue::from(1)))
+    );
+}